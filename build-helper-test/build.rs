@@ -0,0 +1,7 @@
+extern crate regex_gen;
+
+fn main() {
+    if let Err(e) = regex_gen::build_helper::build("patterns.txt") {
+        panic!("{}", e);
+    }
+}
@@ -0,0 +1,52 @@
+// matchers generated from patterns.txt by `regex_gen::build_helper`
+include!(concat!(env!("OUT_DIR"), "/patterns.rs"));
+
+#[cfg(test)]
+mod test {
+    extern crate regex_gen;
+
+    use self::regex_gen::execute_engine::ExecuteEngine;
+    use self::regex_gen::regex_gen::RegexItem;
+    use super::*;
+
+    type Find = fn(&[u8]) -> Option<(usize, usize)>;
+
+    #[test]
+    fn test_patterns() {
+        // patterns are kept as they are written
+        assert_eq!(PATTERNS[2], ("hello", "(?i)hello"));
+        assert_eq!(PATTERNS[3], ("either", "a|b"));
+        assert_eq!(PATTERNS[5], ("empty", ""));
+
+        assert!(ident(b"  foo_1"));
+        assert!(!ident(b"99"));
+        assert!(keyword(b"} else {"));
+        assert!(!keyword(b"elsewhere"));
+        assert!(hello(b"say HeLLo"));
+        assert!(empty(b""));
+        assert!(!never(b"a b ab"));
+        assert_eq!(quoted::find(br#"x = "abc";"#), Some((4, 9)));
+    }
+
+    #[test]
+    fn test_same_as_engine() {
+        let texts = ["", "if x", "a \"b\" c", "while(ab)", "日本語 _x1", "HELLO\nb"];
+        let finds: [Find; 7] = [
+            ident::find,
+            keyword::find,
+            hello::find,
+            either::find,
+            quoted::find,
+            empty::find,
+            never::find,
+        ];
+
+        for (&(name, pattern), find) in PATTERNS.iter().zip(finds.iter()) {
+            let r: RegexItem = pattern.into();
+            let e = ExecuteEngine::new(&r);
+            for text in texts.iter() {
+                assert_eq!(find(text.as_bytes()), e.find(text), "{} in {:?}", name, text);
+            }
+        }
+    }
+}
@@ -0,0 +1,179 @@
+extern crate proc_macro;
+extern crate regex_gen;
+
+use proc_macro::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
+
+use regex_gen::regex_gen::RegexItem;
+use regex_gen::transtable::TransTable;
+
+/// compile a pattern into a matcher when the crate is built, `regex_gen!("a\\d+b")`
+/// is a value with `is_match`, `find` and `as_str`, see `TransTable::to_rust`.
+///
+/// a parse error is a compile error at the literal, with the position inside the
+/// pattern marked in the message. the pattern is shown unescaped, as the parser sees
+/// it, and the offset is counted in bytes of it.
+#[proc_macro]
+pub fn regex_gen(input: TokenStream) -> TokenStream {
+    let lit = match single_literal(input) {
+        Ok(x) => x,
+        Err(span) => return compile_error("expected a string literal", span),
+    };
+
+    let pattern = match unescape(&lit.to_string()) {
+        Some(x) => x,
+        None => return compile_error("expected a string literal", lit.span()),
+    };
+
+    // a span can't point inside of the literal, so the message tells where the offset is from
+    let item: RegexItem = match pattern.parse() {
+        Ok(x) => x,
+        Err(e) => {
+            let message = format!("{}\nthe offset counts bytes from the start of the unescaped pattern", e);
+            return compile_error(&message, lit.span());
+        }
+    };
+
+    let mut t = TransTable::from_nfa(&item.nfa_graph());
+    t.as_dfa();
+    t.minimize();
+
+    matcher(&pattern, &t.to_rust()).parse().unwrap()
+}
+
+// the literal may be wrapped in an invisible group when it comes from `macro_rules`
+fn single_literal(input: TokenStream) -> Result<Literal, Span> {
+    let mut tokens = input.into_iter();
+
+    match (tokens.next(), tokens.next()) {
+        (Some(TokenTree::Literal(x)), None) => Ok(x),
+        (Some(TokenTree::Group(ref x)), None) if x.delimiter() == Delimiter::None => single_literal(x.stream()),
+        (Some(x), _) => Err(x.span()),
+        (None, _) => Err(Span::call_site()),
+    }
+}
+
+fn compile_error(message: &str, span: Span) -> TokenStream {
+    let mut message = Literal::string(message);
+    message.set_span(span);
+
+    let mut bang = Punct::new('!', Spacing::Alone);
+    bang.set_span(span);
+
+    let mut args = Group::new(Delimiter::Parenthesis, TokenTree::from(message).into());
+    args.set_span(span);
+
+    vec![
+        TokenTree::from(Ident::new("compile_error", span)),
+        TokenTree::from(bang),
+        TokenTree::from(args),
+    ]
+    .into_iter()
+    .collect()
+}
+
+// the generated code lives in a module of its own block, so every use gets its own one
+fn matcher(pattern: &str, code: &str) -> String {
+    format!(
+        r#"{{
+    #[allow(dead_code)]
+    mod __regex_gen_matcher {{
+{}
+        pub const PATTERN: &str = {:?};
+
+        pub struct Matcher;
+
+        impl Matcher {{
+            pub fn is_match<T: AsRef<[u8]>>(&self, input: T) -> bool {{
+                is_match(input.as_ref())
+            }}
+
+            pub fn find<T: AsRef<[u8]>>(&self, input: T) -> Option<(usize, usize)> {{
+                find(input.as_ref())
+            }}
+
+            pub fn as_str(&self) -> &'static str {{
+                PATTERN
+            }}
+        }}
+    }}
+
+    __regex_gen_matcher::Matcher
+}}"#,
+        code, pattern
+    )
+}
+
+// value of a string literal as it's written in source, `None` if it's not one
+fn unescape(s: &str) -> Option<String> {
+    if let Some(raw) = s.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        let body = &raw[hashes..raw.len().checked_sub(hashes)?];
+
+        return if body.len() >= 2 && body.starts_with('"') && body.ends_with('"') {
+            Some(body[1..body.len() - 1].to_string())
+        } else {
+            None
+        };
+    }
+
+    if s.len() < 2 || !s.starts_with('"') || !s.ends_with('"') {
+        return None;
+    }
+
+    let mut r = String::new();
+    let mut chars = s[1..s.len() - 1].chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            r.push(c);
+            continue;
+        }
+
+        match chars.next()? {
+            'n' => r.push('\n'),
+            'r' => r.push('\r'),
+            't' => r.push('\t'),
+            '0' => r.push('\0'),
+            '\\' => r.push('\\'),
+            '\'' => r.push('\''),
+            '"' => r.push('"'),
+            'x' => {
+                let hex: String = chars.by_ref().take(2).collect();
+                r.push(u8::from_str_radix(&hex, 16).ok().filter(|&x| x < 0x80)? as char);
+            }
+            'u' => {
+                if chars.next()? != '{' {
+                    return None;
+                }
+                let hex: String = chars.by_ref().take_while(|&x| x != '}').filter(|&x| x != '_').collect();
+                r.push(::std::char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+            }
+            // a line continuation skips the line break and leading whitespaces
+            '\n' => {
+                while chars.peek().is_some_and(|x| x.is_whitespace()) {
+                    chars.next();
+                }
+            }
+            _ => return None,
+        }
+    }
+
+    Some(r)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape(r#""a\\d+b""#), Some(r#"a\d+b"#.to_string()));
+        assert_eq!(unescape(r#""\n\t\"\x41\u{e9}\u{1_F600}""#), Some("\n\t\"Aé\u{1f600}".to_string()));
+        assert_eq!(unescape("\"a\\\n    b\""), Some("ab".to_string()));
+        assert_eq!(unescape(r####"r"\d+""####), Some(r#"\d+"#.to_string()));
+        assert_eq!(unescape(r####"r#"a"b"#"####), Some(r#"a"b"#.to_string()));
+        assert_eq!(unescape(r#"b"ab""#), None);
+        assert_eq!(unescape(r#"'a'"#), None);
+        assert_eq!(unescape(r#"42"#), None);
+        assert_eq!(unescape(r#""\xff""#), None);
+    }
+}
@@ -0,0 +1,8 @@
+extern crate regex_gen_macros;
+
+use regex_gen_macros::regex_gen;
+
+fn main() {
+    let _ = regex_gen!(b"a+");
+    let _ = regex_gen!("a", "b");
+}
@@ -0,0 +1,9 @@
+extern crate regex_gen_macros;
+
+use regex_gen_macros::regex_gen;
+
+fn main() {
+    // the offset is in the unescaped pattern `\d(a`
+    let _ = regex_gen!("\\d(a");
+    let _ = regex_gen!(r#"ab]?+"#);
+}
@@ -0,0 +1,81 @@
+extern crate regex_gen;
+extern crate regex_gen_macros;
+
+use regex_gen::execute_engine::ExecuteEngine;
+use regex_gen::regex_gen::RegexItem;
+use regex_gen_macros::regex_gen;
+
+macro_rules! pattern {
+    ($e:expr) => {
+        regex_gen!($e)
+    };
+}
+
+#[test]
+fn test_regex_gen() {
+    let m = regex_gen!("a\\d+b");
+    assert_eq!(m.as_str(), r#"a\d+b"#);
+    assert!(m.is_match("xa12b"));
+    assert!(!m.is_match("ab"));
+    assert_eq!(m.find("zza1ba99b"), Some((2, 5)));
+
+    let m = regex_gen!(r#"\bfoo\b|é+"#);
+    assert_eq!(m.find("foox éé foo"), Some((5, 9)));
+    assert_eq!(m.find(b"(foo)"), Some((1, 4)));
+
+    let m = pattern!(r"(?i)hello");
+    assert!(m.is_match("say HeLLo"));
+
+    // patterns without any edge
+    let m = regex_gen!("");
+    assert_eq!(m.find("abc"), Some((0, 0)));
+    let m = regex_gen!(r#"a\bb"#);
+    assert!(!m.is_match("a b ab"));
+}
+
+#[test]
+fn test_same_as_engine() {
+    let texts = ["", "abc", "a1b2c3", "foo bar\nbaz", "日本語 text", "x__y", "aaab", "abcd"];
+
+    macro_rules! check {
+        ($($s:expr),*) => {
+            $(
+                let m = regex_gen!($s);
+                let r: RegexItem = $s.into();
+                let e = ExecuteEngine::new(&r);
+                for text in texts.iter() {
+                    assert_eq!(m.find(text), e.find(text), "{} in {:?}", $s, text);
+                }
+            )*
+        };
+    }
+
+    check!(r#"[a-z]+"#, r#"\d"#, r#"(?m)^\w+$"#, r#"\B.\B"#, r#"[^ -~]+"#, r#"b*"#);
+    // matches which start later or end earlier are passed over
+    check!(r#"a+b|a"#, r#"abc|bcd|b"#, r#"a1|1b2c"#, r#"\bx_|_y"#);
+}
+
+#[test]
+fn test_utf8() {
+    macro_rules! check {
+        ($s:expr, $texts:expr) => {
+            let m = regex_gen!($s);
+            let r: RegexItem = $s.into();
+            let e = ExecuteEngine::new(&r);
+            for text in $texts.iter() {
+                assert_eq!(m.find(text), e.find(text), "{} in {:?}", $s, text);
+            }
+        };
+    }
+
+    check!(r#"a(b|c)*d"#, ["abcbd", "xxadyy", "ab"]);
+    check!(r#"[^a]+"#, ["aaa", "aéb", "日本語a", "\u{10ffff}"]);
+    check!(r#"é|ê+|\u{800}"#, ["eéê", "xêê", "\u{800}", "\u{7ff}\u{801}"]);
+    check!(r#"\bfoo\b"#, ["foo", "foobar foo", "(foo)", "éfooé"]);
+    check!(r#"(?m)^\w+$"#, ["ab\ncd", "a b\n", "x"]);
+    check!(r#"x*"#, ["", "é", "axx"]);
+    check!(r#"."#, ["\n", "日"]);
+
+    // invalid utf-8 is never matched
+    assert_eq!(regex_gen!(r#"."#).find(b"\xff\xc3"), None);
+}
@@ -0,0 +1,204 @@
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use regex_gen::{RegexItem, RegexParserError};
+use transtable::TransTable;
+
+// names become functions and modules, so keywords are left out
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "false", "fn", "for",
+    "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "self", "static", "struct",
+    "super", "trait", "true", "type", "unsafe", "use", "where", "while", "abstract", "become", "box", "do", "final",
+    "gen", "macro", "override", "priv", "try", "typeof", "unsized", "virtual", "yield",
+];
+
+#[derive(Debug)]
+pub enum PatternsError {
+    Io(io::Error),
+    // line numbers start from 1
+    MissingEquals(usize),
+    InvalidName(usize),
+    DuplicateName(usize),
+    Regex(usize, RegexParserError),
+}
+
+impl fmt::Display for PatternsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PatternsError::Io(e) => write!(f, "{}", e),
+            PatternsError::MissingEquals(line) => write!(f, "line {}: expected `name = pattern`", line),
+            PatternsError::InvalidName(line) => {
+                write!(f, "line {}: name must be a lowercase identifier and not a keyword", line)
+            }
+            PatternsError::DuplicateName(line) => write!(f, "line {}: duplicate name", line),
+            PatternsError::Regex(line, e) => write!(f, "line {}: {}", line, e),
+        }
+    }
+}
+
+impl Error for PatternsError {}
+
+impl From<io::Error> for PatternsError {
+    fn from(e: io::Error) -> PatternsError {
+        PatternsError::Io(e)
+    }
+}
+
+fn is_name(s: &str) -> bool {
+    let mut chars = s.chars();
+
+    chars.next().is_some_and(|c| c == '_' || c.is_ascii_lowercase())
+        && chars.all(|c| c == '_' || c.is_ascii_lowercase() || c.is_ascii_digit())
+        && s != "_"
+        && !KEYWORDS.contains(&s)
+}
+
+// `name = pattern` lines, blank lines and lines starting with `#` are skipped.
+// whitespaces around the name and the pattern are trimmed, the pattern is kept as written.
+fn parse_patterns(text: &str) -> Result<Vec<(String, String, RegexItem)>, PatternsError> {
+    let mut r: Vec<(String, String, RegexItem)> = vec![];
+
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (name, pattern) = match line.find('=') {
+            Some(x) => (line[..x].trim(), line[x + 1..].trim()),
+            None => return Err(PatternsError::MissingEquals(line_number)),
+        };
+
+        if !is_name(name) {
+            return Err(PatternsError::InvalidName(line_number));
+        }
+        if r.iter().any(|x| x.0 == name) {
+            return Err(PatternsError::DuplicateName(line_number));
+        }
+
+        let item = pattern.parse().map_err(|e| PatternsError::Regex(line_number, e))?;
+        r.push((name.to_string(), pattern.to_string(), item));
+    }
+
+    Ok(r)
+}
+
+// source of the module, `source` is where the patterns come from
+fn module_source(patterns: &[(String, String, RegexItem)], source: &str) -> String {
+    let mut r = format!("// generated by regex-gen from {}, do not edit\n\n", source);
+
+    r.push_str("/// names and source patterns\n");
+    r.push_str("#[allow(dead_code)]\n");
+    r.push_str("pub const PATTERNS: &[(&str, &str)] = &[\n");
+    for (name, pattern, _) in patterns {
+        r.push_str(&format!("    ({:?}, {:?}),\n", name, pattern));
+    }
+    r.push_str("];\n");
+
+    for (name, pattern, item) in patterns {
+        let mut t = TransTable::from_nfa(&item.nfa_graph());
+        t.as_dfa();
+        t.minimize();
+
+        r.push_str(&format!("\n/// if `{}` matches somewhere in the input\n", pattern));
+        r.push_str("#[allow(dead_code)]\n");
+        r.push_str(&format!("pub fn {}(input: &[u8]) -> bool {{\n", name));
+        r.push_str(&format!("    {}::is_match(input)\n", name));
+        r.push_str("}\n\n");
+        r.push_str("#[allow(dead_code)]\n");
+        r.push_str(&format!("pub mod {} {{\n", name));
+        for line in t.to_rust().lines() {
+            if line.is_empty() {
+                r.push('\n');
+            } else {
+                r.push_str(&format!("    {}\n", line));
+            }
+        }
+        r.push_str("}\n");
+    }
+
+    r
+}
+
+/// compile the patterns file into the module at `out`. for each name there is a
+/// `fn name(input: &[u8]) -> bool` and a module `name` with `is_match` and `find`,
+/// see `TransTable::to_rust`. `out` is only written if its content changes.
+pub fn generate<P: AsRef<Path>, Q: AsRef<Path>>(patterns: P, out: Q) -> Result<(), PatternsError> {
+    let text = fs::read_to_string(patterns.as_ref())?;
+    let source = patterns.as_ref().file_name().map_or(String::new(), |x| x.to_string_lossy().into_owned());
+    let code = module_source(&parse_patterns(&text)?, &source);
+
+    if fs::read_to_string(out.as_ref()).ok().as_ref() != Some(&code) {
+        fs::write(out.as_ref(), code)?;
+    }
+
+    Ok(())
+}
+
+/// for build scripts: generate `$OUT_DIR/<file stem>.rs` from the patterns file and
+/// ask cargo to rerun only when it changes. include the module by
+/// `include!(concat!(env!("OUT_DIR"), "/patterns.rs"));`
+pub fn build<P: AsRef<Path>>(patterns: P) -> Result<PathBuf, PatternsError> {
+    let patterns = patterns.as_ref();
+    let stem = patterns.file_stem().unwrap_or_else(|| "patterns".as_ref());
+    let out = Path::new(&env::var_os("OUT_DIR").expect("OUT_DIR is set by cargo")).join(stem).with_extension("rs");
+
+    println!("cargo:rerun-if-changed={}", patterns.display());
+    generate(patterns, &out)?;
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use build_helper::*;
+
+    #[test]
+    fn test_parse_patterns() {
+        let r = parse_patterns("# comment\n\nident = [a-z_]\\w*\n  number=\\d+  \nhello = (?i)hello|hi\n").unwrap();
+        let names: Vec<&str> = r.iter().map(|x| x.0.as_str()).collect();
+        assert_eq!(names, vec!["ident", "number", "hello"]);
+        assert_eq!(r[1].1, "\\d+");
+        assert_eq!(r[2].1, "(?i)hello|hi");
+
+        let error = |s: &str| parse_patterns(s).unwrap_err().to_string();
+        assert_eq!(error("a = x\nb"), "line 2: expected `name = pattern`");
+        assert_eq!(error("type = x"), "line 1: name must be a lowercase identifier and not a keyword");
+        assert_eq!(error("gen = x"), "line 1: name must be a lowercase identifier and not a keyword");
+        assert_eq!(error("Ident = x"), "line 1: name must be a lowercase identifier and not a keyword");
+        assert_eq!(error("1a = x"), "line 1: name must be a lowercase identifier and not a keyword");
+        assert_eq!(error("a = x\n\na = y"), "line 3: duplicate name");
+        assert!(error("a = (x").starts_with("line 1: regex parse error: unclosed group at offset 0"));
+    }
+
+    #[test]
+    fn test_generate() {
+        let dir = env::temp_dir().join(format!("regex-gen-build-helper-{}", ::std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let patterns = dir.join("patterns.txt");
+        let out = dir.join("patterns.rs");
+
+        fs::write(&patterns, "ident = [a-z_]\\w*\nquoted = \"[^\"]*\"\n").unwrap();
+        generate(&patterns, &out).unwrap();
+
+        // the generated code is compiled and run by the build-helper-test crate
+        let code = fs::read_to_string(&out).unwrap();
+        assert!(code.starts_with("// generated by regex-gen from patterns.txt"));
+
+        // unchanged output is not written again
+        let modified = fs::metadata(&out).unwrap().modified().unwrap();
+        ::std::thread::sleep(::std::time::Duration::from_millis(20));
+        generate(&patterns, &out).unwrap();
+        assert_eq!(fs::metadata(&out).unwrap().modified().unwrap(), modified);
+
+        fs::write(&patterns, "ident = (").unwrap();
+        assert!(generate(&patterns, &out).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
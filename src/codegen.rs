@@ -0,0 +1,365 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use node::*;
+use transtable::TransTable;
+
+// utf-8 sequences of the characters from `s` to `e`, each is a list of byte ranges
+fn utf8_sequences(s: u32, e: u32, r: &mut Vec<Vec<(u8, u8)>>) {
+    // surrogates are not characters, but a range may span them
+    if s < 0xd800 && e > 0xdfff {
+        utf8_sequences(s, 0xd7ff, r);
+        utf8_sequences(0xe000, e, r);
+        return;
+    }
+
+    // both ends must be encoded by the same number of bytes
+    for &max in &[0x7f, 0x7ff, 0xffff] {
+        if s <= max && e > max {
+            utf8_sequences(s, max, r);
+            utf8_sequences(max + 1, e, r);
+            return;
+        }
+    }
+
+    if e <= 0x7f {
+        r.push(vec![(s as u8, e as u8)]);
+        return;
+    }
+
+    // every trailing byte must cover a whole range, or be the same on both ends
+    let len = ::std::char::from_u32(s).unwrap().len_utf8();
+    for i in 1..len {
+        let m = (1 << (6 * i)) - 1;
+        if s & !m != e & !m {
+            if s & m != 0 {
+                utf8_sequences(s, s | m, r);
+                utf8_sequences((s | m) + 1, e, r);
+                return;
+            }
+            if e & m != m {
+                utf8_sequences(s, (e & !m) - 1, r);
+                utf8_sequences(e & !m, e, r);
+                return;
+            }
+        }
+    }
+
+    let (mut a, mut b) = ([0; 4], [0; 4]);
+    let a = ::std::char::from_u32(s).unwrap().encode_utf8(&mut a).as_bytes();
+    let b = ::std::char::from_u32(e).unwrap().encode_utf8(&mut b).as_bytes();
+    r.push(a.iter().cloned().zip(b.iter().cloned()).collect());
+}
+
+// byte ranges of a utf-8 sequence, and the state it leads to
+type Sequence = (Vec<(u8, u8)>, usize);
+
+// the table over bytes of utf-8, states of the dfa keep their order at the front,
+// the states inside of multibyte characters follow them.
+struct ByteDfa {
+    start: [usize; 4],
+    accept: Vec<u8>,
+    trans: Vec<Vec<(u8, u8, usize)>>,
+
+    // states inside of characters by what is left of their sequences
+    inner: HashMap<Vec<Sequence>, usize>,
+}
+
+impl ByteDfa {
+    fn new(table: &TransTable) -> ByteDfa {
+        let mut states: Vec<&States> = table.trans_map().keys().collect();
+        states.sort();
+        let index: HashMap<&States, usize> = states.iter().enumerate().map(|(i, &x)| (x, i)).collect();
+
+        let mut r = ByteDfa {
+            start: [0; 4],
+            accept: vec![0; states.len()],
+            trans: vec![vec![]; states.len()],
+            inner: HashMap::new(),
+        };
+
+        for (i, &ctx) in CONTEXTS.iter().enumerate() {
+            r.start[i] = index[table.start_in(ctx)];
+            for (q, &state) in states.iter().enumerate() {
+                if table.is_end_in(state, ctx) {
+                    r.accept[q] |= ctx.mask();
+                }
+            }
+        }
+
+        for (q, &state) in states.iter().enumerate() {
+            let mut sequences = vec![];
+            for e in &table.trans_map()[state] {
+                let dest = index[e.next_node()];
+                for (s, e) in e.matches().as_ref().map_or(vec![], |x| x.ranges()) {
+                    let mut v = vec![];
+                    utf8_sequences(s as u32, e as u32, &mut v);
+                    sequences.extend(v.into_iter().map(|x| (x, dest)));
+                }
+            }
+
+            r.append_sequences(q, &sequences);
+        }
+
+        r
+    }
+
+    // subset construction over the bytes of `sequences` which leave state `q`. utf-8 is
+    // prefix free, so a finished sequence is never a prefix of another one.
+    fn append_sequences(&mut self, q: usize, sequences: &[Sequence]) {
+        let mut visit = vec![(q, 0, (0..sequences.len()).collect::<Vec<usize>>())];
+
+        while let Some((id, pos, active)) = visit.pop() {
+            let mut bounds: Vec<u16> = active
+                .iter()
+                .flat_map(|&i| {
+                    let (s, e) = sequences[i].0[pos];
+                    vec![s as u16, e as u16 + 1]
+                })
+                .collect();
+            bounds.sort();
+            bounds.dedup();
+
+            for w in bounds.windows(2) {
+                let (s, e) = (w[0] as u8, (w[1] - 1) as u8);
+                let next: Vec<usize> = active
+                    .iter()
+                    .cloned()
+                    .filter(|&i| sequences[i].0[pos].0 <= s && s <= sequences[i].0[pos].1)
+                    .collect();
+                if next.is_empty() {
+                    continue;
+                }
+
+                let dest = match next.iter().find(|&&i| sequences[i].0.len() == pos + 1) {
+                    Some(&i) => sequences[i].1,
+                    None => {
+                        let key = next.iter().map(|&i| (sequences[i].0[pos + 1..].to_vec(), sequences[i].1)).collect();
+                        match self.inner.get(&key) {
+                            Some(&x) => x,
+                            None => {
+                                let x = self.trans.len();
+                                self.trans.push(vec![]);
+                                self.accept.push(0);
+                                self.inner.insert(key, x);
+                                visit.push((x, pos + 1, next));
+                                x
+                            }
+                        }
+                    }
+                };
+
+                match self.trans[id].last_mut() {
+                    Some(last) if last.2 == dest && last.1 as u16 + 1 == s as u16 => last.1 = e,
+                    _ => self.trans[id].push((s, e, dest)),
+                }
+            }
+        }
+    }
+}
+
+fn byte_literal(b: u8) -> String {
+    match b {
+        b'\'' | b'\\' => format!("b'\\{}'", b as char),
+        b' '..=b'~' => format!("b'{}'", b as char),
+        _ => format!("0x{:02x}", b),
+    }
+}
+
+// `fn step(state: usize, b: u8) -> Option<usize>`, a nested match over the states and bytes
+fn write_step(r: &mut String, dfa: &ByteDfa) {
+    // a table without edges never steps, a match on the state would be unreachable code
+    if dfa.trans.iter().all(|x| x.is_empty()) {
+        writeln!(r, "fn step(_state: usize, _b: u8) -> Option<usize> {{").unwrap();
+        writeln!(r, "    None").unwrap();
+        writeln!(r, "}}").unwrap();
+        return;
+    }
+
+    writeln!(r, "fn step(state: usize, b: u8) -> Option<usize> {{").unwrap();
+    writeln!(r, "    let next = match state {{").unwrap();
+    for (q, edges) in dfa.trans.iter().enumerate().filter(|(_, x)| !x.is_empty()) {
+        // byte ranges which go to the same state share an arm
+        let mut arms: Vec<(usize, Vec<String>)> = vec![];
+        for &(s, e, dest) in edges {
+            let pattern = if s == e {
+                byte_literal(s)
+            } else {
+                format!("{}..={}", byte_literal(s), byte_literal(e))
+            };
+
+            match arms.iter_mut().find(|x| x.0 == dest) {
+                Some(x) => x.1.push(pattern),
+                None => arms.push((dest, vec![pattern])),
+            }
+        }
+
+        writeln!(r, "        {} => match b {{", q).unwrap();
+        for (dest, patterns) in arms {
+            writeln!(r, "            {} => {},", patterns.join(" | "), dest).unwrap();
+        }
+        writeln!(r, "            _ => return None,").unwrap();
+        writeln!(r, "        }},").unwrap();
+    }
+    writeln!(r, "        _ => return None,").unwrap();
+    writeln!(r, "    }};").unwrap();
+    writeln!(r).unwrap();
+    writeln!(r, "    Some(next)").unwrap();
+    writeln!(r, "}}").unwrap();
+}
+
+const SEARCH: &str = r#"
+// the class of the byte next to a position, start or end of input, newline, word or other
+fn context(b: Option<u8>) -> usize {
+    match b {
+        None => 0,
+        Some(b'\n') => 1,
+        Some(b'0'..=b'9') | Some(b'A'..=b'Z') | Some(b'_') | Some(b'a'..=b'z') => 2,
+        Some(_) => 3,
+    }
+}
+
+/// byte range of the leftmost-longest match. the input is read once, with a thread for
+/// every start which may still match, at most one for each state, the earliest started one.
+pub fn find(input: &[u8]) -> Option<(usize, usize)> {
+    let mut threads: Vec<(usize, usize)> = Vec::new();
+    let mut best: Option<(usize, usize)> = None;
+    let mut i = 0;
+
+    loop {
+        let prev = if i == 0 { None } else { Some(input[i - 1]) };
+        let next = input.get(i).cloned();
+
+        // a match starts at a character boundary, no need to start one after a match is found
+        if best.is_none() && next.is_none_or(|b| b & 0xc0 != 0x80) {
+            let state = START[context(prev)];
+            if threads.iter().all(|&(_, x)| x != state) {
+                threads.push((i, state));
+            }
+        }
+
+        for &(from, state) in &threads {
+            if ACCEPT[state] & 1 << context(next) != 0 && best.is_none_or(|(s, _)| from <= s) {
+                best = Some((from, i));
+            }
+        }
+
+        if let Some((s, _)) = best {
+            threads.retain(|&(from, _)| from <= s);
+        }
+
+        let b = match next {
+            Some(b) if !threads.is_empty() || best.is_none() => b,
+            _ => return best,
+        };
+
+        let mut stepped: Vec<(usize, usize)> = Vec::with_capacity(threads.len());
+        for (from, state) in threads {
+            if let Some(x) = step(state, b) {
+                if stepped.iter().all(|&(_, y)| y != x) {
+                    stepped.push((from, x));
+                }
+            }
+        }
+        threads = stepped;
+        i += 1;
+    }
+}
+
+pub fn is_match(input: &[u8]) -> bool {
+    find(input).is_some()
+}
+"#;
+
+impl TransTable {
+    /// rust source of `pub fn is_match(input: &[u8]) -> bool` and
+    /// `pub fn find(input: &[u8]) -> Option<(usize, usize)>`, which search the
+    /// same way as `ExecuteEngine` over utf-8 bytes. the code has no dependency,
+    /// it's meant to be wrapped in a module. the table must be a dfa.
+    pub fn to_rust(&self) -> String {
+        let dfa = ByteDfa::new(self);
+        let mut r = String::new();
+
+        writeln!(r, "// generated by regex-gen, a state machine over utf-8 bytes").unwrap();
+        writeln!(r).unwrap();
+        writeln!(r, "// start state after each context").unwrap();
+        writeln!(
+            r,
+            "const START: [usize; 4] = [{}];",
+            dfa.start.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(", ")
+        )
+        .unwrap();
+        writeln!(r).unwrap();
+        writeln!(r, "// contexts which may follow a match which ends in each state").unwrap();
+        writeln!(
+            r,
+            "const ACCEPT: [u8; {}] = [{}];",
+            dfa.accept.len(),
+            dfa.accept.iter().map(|x| format!("0b{:04b}", x)).collect::<Vec<_>>().join(", ")
+        )
+        .unwrap();
+        writeln!(r).unwrap();
+
+        write_step(&mut r, &dfa);
+        r.push_str(SEARCH);
+
+        r
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use codegen::*;
+    use transtable::dfa;
+
+    #[test]
+    fn test_utf8_sequences() {
+        let sequences = |s: char, e: char| {
+            let mut r = vec![];
+            utf8_sequences(s as u32, e as u32, &mut r);
+            r
+        };
+
+        assert_eq!(sequences('a', 'z'), vec![vec![(b'a', b'z')]]);
+        assert_eq!(
+            sequences('\u{7f}', '\u{80}'),
+            vec![vec![(0x7f, 0x7f)], vec![(0xc2, 0xc2), (0x80, 0x80)]]
+        );
+        assert_eq!(
+            sequences('\u{0}', char::MAX),
+            vec![
+                vec![(0x00, 0x7f)],
+                vec![(0xc2, 0xdf), (0x80, 0xbf)],
+                vec![(0xe0, 0xe0), (0xa0, 0xbf), (0x80, 0xbf)],
+                vec![(0xe1, 0xec), (0x80, 0xbf), (0x80, 0xbf)],
+                vec![(0xed, 0xed), (0x80, 0x9f), (0x80, 0xbf)],
+                vec![(0xee, 0xef), (0x80, 0xbf), (0x80, 0xbf)],
+                vec![(0xf0, 0xf0), (0x90, 0xbf), (0x80, 0xbf), (0x80, 0xbf)],
+                vec![(0xf1, 0xf3), (0x80, 0xbf), (0x80, 0xbf), (0x80, 0xbf)],
+                vec![(0xf4, 0xf4), (0x80, 0x8f), (0x80, 0xbf), (0x80, 0xbf)],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_byte_dfa() {
+        // the second byte of 'é' is read in a state of its own
+        let b = ByteDfa::new(&dfa(r#"a|é"#));
+        assert_eq!(b.start, [1; 4]);
+        assert_eq!(b.accept, vec![0b1111, 0, 0]);
+        assert_eq!(b.trans, vec![vec![], vec![(b'a', b'a', 0), (0xc3, 0xc3, 2)], vec![(0xa9, 0xa9, 0)]]);
+
+        // no word may precede the match
+        let b = ByteDfa::new(&dfa(r#"\bx"#));
+        for (i, &ctx) in CONTEXTS.iter().enumerate() {
+            assert_eq!(b.trans[b.start[i]].is_empty(), ctx == Context::Word);
+        }
+    }
+
+    #[test]
+    fn test_to_rust() {
+        assert!(!dfa(r#"[a-c]|é"#).to_rust().contains("regex_gen"));
+        // nothing to match on for a table without edges
+        assert!(!dfa(r#""#).to_rust().contains("match state"));
+    }
+}
@@ -0,0 +1,318 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use node::*;
+use transtable::TransTable;
+
+/// strings to test a pattern with, see `TransTable::corpus`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Corpus {
+    accepted: Vec<String>,
+    rejected: Vec<String>,
+}
+
+impl Corpus {
+    /// together they go through every edge which can lead to an end, and every end
+    pub fn accepted(&self) -> &[String] {
+        &self.accepted
+    }
+
+    /// for every reachable state, the shortest string to it followed by a character without
+    /// an edge, or stopping there if it is not an end and all characters have edges.
+    pub fn rejected(&self) -> &[String] {
+        &self.rejected
+    }
+}
+
+/// the edit which turns an accepted string into a near miss
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mutation {
+    Substitute,
+    Insert,
+    Delete,
+    Truncate,
+}
+
+// the character shown for an edge
+fn edge_char(e: &Edge) -> char {
+    representative(&e.matches().as_ref().unwrap().ranges()).unwrap()
+}
+
+impl TransTable {
+    /// a small set of accepted strings covering the table, and near misses which
+    /// die in each state. the table must be a dfa, see `TransTable::as_dfa`.
+    pub fn corpus(&self) -> Corpus {
+        let mut states: Vec<&States> = self.trans_map().keys().collect();
+        states.sort();
+
+        let prefix = self.shortest_prefixes();
+        let suffix = self.shortest_suffixes();
+
+        // the longest candidates go first, they cover more edges on the way
+        let mut candidates: Vec<(&States, &Edge, String)> = vec![];
+        for &state in states.iter().filter(|x| prefix.contains_key(*x)) {
+            for e in &self.trans_map()[state] {
+                if let Some(rest) = suffix.get(e.next_node()) {
+                    let s = format!("{}{}{}", prefix[state], edge_char(e), rest);
+                    candidates.push((state, e, s));
+                }
+            }
+        }
+        candidates.sort_by_key(|x| ::std::cmp::Reverse(x.2.chars().count()));
+
+        let mut accepted = vec![];
+        let mut covered: HashSet<(&States, &States)> = HashSet::new();
+        let mut ends: HashSet<&States> = HashSet::new();
+        for (state, e, s) in candidates {
+            if covered.contains(&(state, e.next_node())) {
+                continue;
+            }
+
+            let mut q = self.start_id();
+            for c in s.chars() {
+                let next = self.next_state(q, c).unwrap();
+                covered.insert((q, next));
+                q = next;
+            }
+            ends.insert(q);
+            accepted.push(s);
+        }
+
+        for &state in states.iter().filter(|&x| self.end_set().contains(*x) && !ends.contains(x)) {
+            if let Some(s) = prefix.get(state) {
+                accepted.push(s.clone());
+            }
+        }
+
+        let mut reachable: Vec<&States> = prefix.keys().cloned().collect();
+        reachable.sort_by_key(|x| (prefix[x].chars().count(), &prefix[x]));
+
+        let mut rejected = vec![];
+        for state in reachable {
+            let ranges = normalize_ranges(
+                self.trans_map()[state]
+                    .iter()
+                    .flat_map(|e| e.matches().as_ref().unwrap().ranges())
+                    .collect(),
+            );
+
+            match representative(&complement_ranges(&ranges)) {
+                Some(c) => rejected.push(format!("{}{}", prefix[state], c)),
+                None if !self.end_set().contains(state) => rejected.push(prefix[state].clone()),
+                None => {}
+            }
+        }
+
+        Corpus { accepted, rejected }
+    }
+
+    /// strings one edit away from `accepted` which are rejected by the table,
+    /// with the edit made. the table must be a dfa, see `TransTable::as_dfa`.
+    pub fn near_misses<S: AsRef<str>>(&self, accepted: &[S]) -> Vec<(Mutation, String)> {
+        let mut r = vec![];
+        let mut seen = HashSet::new();
+        let mut push = |m: Mutation, s: String| {
+            if !self.accepts(&s) && seen.insert(s.clone()) {
+                r.push((m, s));
+            }
+        };
+
+        for s in accepted {
+            let chars: Vec<char> = s.as_ref().chars().collect();
+
+            // characters which take each state to a different place, or nowhere
+            let mut candidates = vec![];
+            let mut q = Some(self.start_id());
+            for i in 0..=chars.len() {
+                candidates.push(q.map_or(vec![], |x| self.interesting_chars(x)));
+                q = q.and_then(|x| self.next_state(x, *chars.get(i)?));
+            }
+
+            for (i, c) in chars.iter().enumerate() {
+                for &x in candidates[i].iter().filter(|&x| x != c) {
+                    let mut v = chars.clone();
+                    v[i] = x;
+                    push(Mutation::Substitute, v.into_iter().collect());
+                }
+            }
+
+            for (i, candidates) in candidates.iter().enumerate() {
+                for &x in candidates {
+                    let mut v = chars.clone();
+                    v.insert(i, x);
+                    push(Mutation::Insert, v.into_iter().collect());
+                }
+            }
+
+            for i in 0..chars.len() {
+                let mut v = chars.clone();
+                v.remove(i);
+                push(Mutation::Delete, v.into_iter().collect());
+            }
+
+            for i in 0..chars.len() {
+                push(Mutation::Truncate, chars[..i].iter().collect());
+            }
+        }
+
+        r
+    }
+
+    // a character of every range of the edges, and of the characters without an edge
+    fn interesting_chars(&self, state: &States) -> Vec<char> {
+        let edges: Vec<Vec<(char, char)>> = self.trans_map()[state]
+            .iter()
+            .map(|e| e.matches().as_ref().unwrap().ranges())
+            .collect();
+        let missing = complement_ranges(&normalize_ranges(edges.iter().flat_map(|x| x.iter().cloned()).collect()));
+
+        edges
+            .iter()
+            .chain(Some(&missing))
+            .flat_map(|x| x.iter())
+            .filter_map(|&x| representative(&[x]))
+            .collect()
+    }
+
+    // the shortest string which leads to every reachable state
+    fn shortest_prefixes(&self) -> HashMap<&States, String> {
+        let mut r = HashMap::new();
+        let mut queue = VecDeque::new();
+        r.insert(self.start_id(), String::new());
+        queue.push_back(self.start_id());
+
+        while let Some(state) = queue.pop_front() {
+            let mut edges: Vec<(char, &States)> = self.trans_map()[state].iter().map(|e| (edge_char(e), e.next_node())).collect();
+            edges.sort();
+
+            for (c, next) in edges {
+                if !r.contains_key(next) {
+                    let s = format!("{}{}", r[state], c);
+                    r.insert(next, s);
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        r
+    }
+
+    // the shortest string which leads from every state to an end, states which
+    // can't reach an end are left out
+    fn shortest_suffixes(&self) -> HashMap<&States, String> {
+        let mut reverse: HashMap<&States, Vec<(char, &States)>> = HashMap::new();
+        for (state, edges) in self.trans_map() {
+            for e in edges {
+                reverse.entry(e.next_node()).or_default().push((edge_char(e), state));
+            }
+        }
+
+        let mut ends: Vec<&States> = self.end_set().iter().collect();
+        ends.sort();
+
+        let mut r: HashMap<&States, String> = HashMap::new();
+        let mut queue = VecDeque::new();
+        for state in ends {
+            r.insert(state, String::new());
+            queue.push_back(state);
+        }
+
+        while let Some(state) = queue.pop_front() {
+            let mut edges = reverse.remove(state).unwrap_or_default();
+            edges.sort();
+
+            for (c, prev) in edges {
+                if !r.contains_key(prev) {
+                    let s = format!("{}{}", c, r[state]);
+                    r.insert(prev, s);
+                    queue.push_back(prev);
+                }
+            }
+        }
+
+        r
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use corpus::*;
+    use execute_engine::*;
+    use transtable::dfa;
+
+    #[test]
+    fn test_corpus() {
+        let c = dfa(r#"a(b|c)*d"#).corpus();
+        assert_eq!(c.accepted(), ["abd"]);
+        assert_eq!(c.rejected(), [" ", "a ", "ad "]);
+
+        for s in &[
+            r#"a(b|c)*d"#,
+            r#"(foo|bar)+(\.\d{2})?"#,
+            r#"[^\dab]+"#,
+            r#"x?|y"#,
+            r#"[a-z_][a-z0-9_]*"#,
+        ] {
+            let t = dfa(s);
+            let c = t.corpus();
+            let e = ExecuteEngine::with_transtable(dfa(s));
+
+            let mut edges = HashSet::new();
+            for x in c.accepted() {
+                assert!(e.exact_match(x), "{} should match {}", x, s);
+
+                let mut q = t.start_id();
+                for ch in x.chars() {
+                    let next = t.next_state(q, ch).unwrap();
+                    edges.insert((q.clone(), next.clone()));
+                    q = next;
+                }
+            }
+            assert_eq!(edges.len(), t.edge_count(), "{}", s);
+
+            for x in c.rejected() {
+                assert!(!e.exact_match(x), "{} shouldn't match {}", x, s);
+            }
+        }
+
+        // edges from the starts after other contexts are not covered
+        let c = dfa(r#"\bab|a$"#).corpus();
+        assert_eq!(c.accepted(), ["ab", "a"]);
+        assert_eq!(c.rejected(), [" ", "a ", "ab "]);
+
+        // every character has an edge, so only the state which is not an end is left
+        assert!(dfa(r#"[\s\S]*"#).corpus().rejected().is_empty());
+        assert_eq!(dfa(r#"[\s\S]"#).corpus().rejected(), ["", "  "]);
+    }
+
+    #[test]
+    fn test_near_misses() {
+        let t = dfa(r#"[^\dab]+"#);
+        let e = ExecuteEngine::with_transtable(dfa(r#"[^\dab]+"#));
+        let r = t.near_misses(&["x"]);
+
+        for (_, x) in &r {
+            assert!(!e.exact_match(x), "{} shouldn't match", x);
+        }
+        assert_eq!(
+            r,
+            vec![
+                (Mutation::Substitute, "0".to_string()),
+                (Mutation::Substitute, "a".to_string()),
+                (Mutation::Insert, "0x".to_string()),
+                (Mutation::Insert, "ax".to_string()),
+                (Mutation::Insert, "x0".to_string()),
+                (Mutation::Insert, "xa".to_string()),
+                (Mutation::Delete, "".to_string()),
+            ]
+        );
+
+        let t = dfa(r#"\d{3}-\d{4}"#);
+        let r = t.near_misses(t.corpus().accepted());
+        assert!(r.contains(&(Mutation::Truncate, "000-".to_string())));
+        assert!(r.contains(&(Mutation::Substitute, "000 0000".to_string())));
+        assert!(r.contains(&(Mutation::Insert, "0000-0000".to_string())));
+        assert!(r.contains(&(Mutation::Delete, "00-0000".to_string())));
+    }
+}
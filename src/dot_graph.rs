@@ -19,7 +19,7 @@ impl ToDotGraph for TransTable {
                 s.push_str(&format!("\t{} -> {} [label=\"{}\"];\n",
                                     state.iter().join(","),
                                     edge.next_node().iter().join(","),
-                                    edge.matches().as_ref().unwrap().to_string()));
+                                    edge.matches().as_ref().unwrap()));
             }
         }
 
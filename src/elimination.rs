@@ -0,0 +1,403 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use node::*;
+use regex_gen::{RegexAnnotation, RegexItem, RegexUnit};
+use transtable::TransTable;
+
+// regular expressions built while eliminating states, constructors below keep them simple
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Epsilon,
+    Chars(Vec<(char, char)>),
+    Concat(Vec<Expr>),
+    // never contains `Epsilon`, it becomes `Optional` instead
+    Alternation(Vec<Expr>),
+    Optional(Box<Expr>),
+    Star(Box<Expr>),
+    Plus(Box<Expr>),
+}
+
+fn concat(list: Vec<Expr>) -> Expr {
+    let mut r: Vec<Expr> = vec![];
+
+    for x in list {
+        match x {
+            Expr::Epsilon => {}
+            Expr::Concat(list) => r.extend(list),
+            // `x x*` is `x+`
+            Expr::Star(x) => {
+                let prefix = match *x {
+                    Expr::Concat(ref list) => list.clone(),
+                    ref x => vec![x.clone()],
+                };
+
+                if r.ends_with(&prefix) {
+                    let len = r.len() - prefix.len();
+                    r.truncate(len);
+                    r.push(Expr::Plus(x));
+                } else {
+                    r.push(Expr::Star(x));
+                }
+            }
+            x => r.push(x),
+        }
+    }
+
+    match r.len() {
+        0 => Expr::Epsilon,
+        1 => r.pop().unwrap(),
+        _ => Expr::Concat(r),
+    }
+}
+
+fn alternation(list: Vec<Expr>) -> Expr {
+    let mut r: Vec<Expr> = vec![];
+    let mut optional = false;
+    let mut chars: Option<usize> = None;
+
+    let mut visit = list;
+    while let Some(x) = visit.pop() {
+        match x {
+            Expr::Epsilon => optional = true,
+            Expr::Optional(x) => {
+                optional = true;
+                visit.push(*x);
+            }
+            Expr::Alternation(list) => visit.extend(list),
+            // classes are merged into the first one
+            Expr::Chars(ranges) => match chars {
+                Some(i) => {
+                    if let Expr::Chars(ref mut x) = r[i] {
+                        x.extend(ranges);
+                        *x = normalize_ranges(x.clone());
+                    }
+                }
+                None => {
+                    chars = Some(r.len());
+                    r.push(Expr::Chars(ranges));
+                }
+            },
+            x => {
+                if !r.contains(&x) {
+                    r.push(x);
+                }
+            }
+        }
+    }
+    r.reverse();
+
+    let r = match r.len() {
+        0 => Expr::Epsilon,
+        1 => r.pop().unwrap(),
+        _ => Expr::Alternation(r),
+    };
+
+    if optional {
+        self::optional(r)
+    } else {
+        r
+    }
+}
+
+fn optional(x: Expr) -> Expr {
+    match x {
+        Expr::Epsilon | Expr::Optional(_) | Expr::Star(_) => x,
+        Expr::Plus(x) => Expr::Star(x),
+        x => Expr::Optional(Box::new(x)),
+    }
+}
+
+fn star(x: Expr) -> Expr {
+    match x {
+        Expr::Epsilon | Expr::Star(_) => x,
+        Expr::Optional(x) | Expr::Plus(x) => Expr::Star(x),
+        x => Expr::Star(Box::new(x)),
+    }
+}
+
+// members of a class, a range of two characters is printed as them
+fn class_members(ranges: &[(char, char)]) -> Vec<RegexUnit> {
+    let mut r = vec![];
+
+    for &(s, e) in ranges {
+        if s == e {
+            r.push(RegexUnit::Character(s));
+        } else if char_after(s) == Some(e) {
+            r.push(RegexUnit::Character(s));
+            r.push(RegexUnit::Character(e));
+        } else {
+            r.push(RegexUnit::CharacterRange(s, e));
+        }
+    }
+
+    r
+}
+
+// a class is negated if it's shorter that way
+fn class_unit(ranges: &[(char, char)]) -> RegexUnit {
+    let mut members = class_members(ranges);
+    let negated = class_members(&complement_ranges(ranges));
+
+    if ranges.is_empty() {
+        RegexUnit::NotUnits(vec![RegexUnit::CharacterRange('\0', char::MAX)])
+    } else if let [RegexUnit::Character(c)] = negated[..] {
+        RegexUnit::NotCharacter(c)
+    } else if negated.len() < members.len() {
+        RegexUnit::NotUnits(negated)
+    } else if members.len() == 1 {
+        members.pop().unwrap()
+    } else {
+        RegexUnit::UnitChoice(members)
+    }
+}
+
+fn to_item(x: &Expr) -> RegexItem {
+    let repeated = |x: &Expr, annotation| {
+        let item = to_item(x);
+        match x {
+            // a sequence or a repeated item needs a group to repeat
+            Expr::Concat(_) | Expr::Optional(_) | Expr::Star(_) | Expr::Plus(_) => {
+                RegexItem::new(RegexUnit::ItemChoice(vec![item]), annotation)
+            }
+            _ => RegexItem::new(item_unit(item), annotation),
+        }
+    };
+
+    match x {
+        Expr::Epsilon => RegexItem::new(RegexUnit::ItemList(vec![]), RegexAnnotation::StandAlone),
+        Expr::Chars(ranges) => RegexItem::new(class_unit(ranges), RegexAnnotation::StandAlone),
+        Expr::Concat(list) => RegexItem::new(
+            RegexUnit::ItemList(list.iter().map(to_item).collect()),
+            RegexAnnotation::StandAlone,
+        ),
+        Expr::Alternation(list) => RegexItem::new(
+            RegexUnit::ItemChoice(list.iter().map(to_item).collect()),
+            RegexAnnotation::StandAlone,
+        ),
+        Expr::Optional(x) => repeated(x, RegexAnnotation::OneOrZero),
+        Expr::Star(x) => repeated(x, RegexAnnotation::AnyOccurs),
+        Expr::Plus(x) => repeated(x, RegexAnnotation::GreaterZero),
+    }
+}
+
+// unit of an item without annotation
+fn item_unit(item: RegexItem) -> RegexUnit {
+    match item.into_parts() {
+        (unit, RegexAnnotation::StandAlone) => unit,
+        (unit, annotation) => RegexUnit::ItemChoice(vec![RegexItem::new(unit, annotation)]),
+    }
+}
+
+// number of classes and operators, it is about the length of the printed regex
+fn size(x: &Expr) -> usize {
+    match x {
+        Expr::Epsilon => 0,
+        Expr::Chars(ranges) => ranges.len().max(1),
+        Expr::Concat(list) | Expr::Alternation(list) => list.iter().map(size).sum::<usize>() + list.len() - 1,
+        Expr::Optional(x) | Expr::Star(x) | Expr::Plus(x) => size(x) + 1,
+    }
+}
+
+/// default limit of `TransTable::to_regex`
+pub const DEFAULT_REGEX_SIZE_LIMIT: usize = 1 << 16;
+
+// edges between the states being eliminated, with the states on both sides of each one
+struct Edges {
+    exprs: HashMap<(usize, usize), Expr>,
+    ins: Vec<BTreeSet<usize>>,
+    outs: Vec<BTreeSet<usize>>,
+}
+
+impl Edges {
+    fn new(states: usize) -> Edges {
+        Edges {
+            exprs: HashMap::new(),
+            ins: vec![BTreeSet::new(); states],
+            outs: vec![BTreeSet::new(); states],
+        }
+    }
+
+    fn connect(&mut self, from: usize, to: usize, x: Expr) -> &Expr {
+        let x = match self.exprs.remove(&(from, to)) {
+            Some(y) => alternation(vec![y, x]),
+            None => x,
+        };
+
+        self.outs[from].insert(to);
+        self.ins[to].insert(from);
+        self.exprs.entry((from, to)).or_insert(x)
+    }
+
+    fn remove(&mut self, from: usize, to: usize) -> Option<Expr> {
+        self.outs[from].remove(&to);
+        self.ins[to].remove(&from);
+        self.exprs.remove(&(from, to))
+    }
+
+    // number of paths through `k`, loops on `k` aside
+    fn paths(&self, k: usize) -> usize {
+        let ins = self.ins[k].len() - self.ins[k].contains(&k) as usize;
+        let outs = self.outs[k].len() - self.outs[k].contains(&k) as usize;
+        ins * outs
+    }
+}
+
+impl TransTable {
+    /// a regex for the strings accepted from start to end of input, by state elimination.
+    /// assertions about the characters around a match are not kept, and an empty language
+    /// gives a class which matches nothing.
+    ///
+    /// the regex may be exponentially larger than the table, `None` if it grows past
+    /// `DEFAULT_REGEX_SIZE_LIMIT`, see `to_regex_with_limit`.
+    pub fn to_regex(&self) -> Option<RegexItem> {
+        self.to_regex_with_limit(DEFAULT_REGEX_SIZE_LIMIT)
+    }
+
+    /// same as `to_regex`, `None` if the regex has more than `limit` classes and operators
+    pub fn to_regex_with_limit(&self, limit: usize) -> Option<RegexItem> {
+        self.eliminate(limit).map(|x| to_item(&x))
+    }
+
+    // expression of the strings from start to end, the edges never grow past `limit`
+    fn eliminate(&self, limit: usize) -> Option<Expr> {
+        // only states which are reachable and lead to an end take part
+        let mut reachable = HashSet::new();
+        let mut visit = vec![self.start_id()];
+        while let Some(state) = visit.pop() {
+            if reachable.insert(state) {
+                visit.extend(self.trans_map()[state].iter().map(|x| x.next_node()));
+            }
+        }
+
+        let mut live: HashSet<&States> = self.end_set().iter().filter(|x| reachable.contains(*x)).collect();
+        loop {
+            let more: Vec<&States> = reachable
+                .iter()
+                .filter(|x| !live.contains(*x) && self.trans_map()[*x].iter().any(|e| live.contains(e.next_node())))
+                .cloned()
+                .collect();
+            if more.is_empty() {
+                break;
+            }
+            live.extend(more);
+        }
+
+        let mut states: Vec<&States> = live.into_iter().collect();
+        states.sort();
+        let index: HashMap<&States, usize> = states.iter().enumerate().map(|(i, &x)| (x, i)).collect();
+
+        // two extra states, a single start and a single end
+        let (start, end) = (states.len(), states.len() + 1);
+        let mut edges = Edges::new(states.len() + 2);
+
+        if let Some(&i) = index.get(self.start_id()) {
+            edges.connect(start, i, Expr::Epsilon);
+        }
+        for (&state, &i) in &index {
+            if self.end_set().contains(state) {
+                edges.connect(i, end, Expr::Epsilon);
+            }
+
+            for e in &self.trans_map()[state] {
+                if let Some(&j) = index.get(e.next_node()) {
+                    let x = match e.matches() {
+                        Some(m) => Expr::Chars(m.ranges()),
+                        None => Expr::Epsilon,
+                    };
+                    if size(edges.connect(i, j, x)) > limit {
+                        return None;
+                    }
+                }
+            }
+        }
+
+        let mut remain: Vec<usize> = (0..states.len()).collect();
+        while !remain.is_empty() {
+            // states with fewer paths through them first, they make smaller expressions
+            let pos = (0..remain.len())
+                .min_by_key(|&x| (edges.paths(remain[x]), ::std::cmp::Reverse(remain[x])))
+                .unwrap();
+            let k = remain.remove(pos);
+
+            let repeat = edges.remove(k, k).map_or(Expr::Epsilon, star);
+            let ins: Vec<(usize, Expr)> = edges.ins[k].clone().into_iter().map(|i| (i, edges.remove(i, k).unwrap())).collect();
+            let outs: Vec<(usize, Expr)> = edges.outs[k].clone().into_iter().map(|j| (j, edges.remove(k, j).unwrap())).collect();
+
+            for (i, x) in &ins {
+                for (j, y) in &outs {
+                    if size(edges.connect(*i, *j, concat(vec![x.clone(), repeat.clone(), y.clone()]))) > limit {
+                        return None;
+                    }
+                }
+            }
+        }
+
+        Some(edges.remove(start, end).unwrap_or(Expr::Chars(vec![])))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use elimination::*;
+    use regex_gen::*;
+    use transtable::*;
+
+    fn to_regex(s: &str) -> String {
+        let mut t = dfa(s);
+        t.minimize();
+        let r = t.to_regex().unwrap();
+
+        // the printed regex parses back to the same language
+        let back: RegexItem = r.to_string().as_str().into();
+        assert!(back.compare(&s.into()).is_equal(), "{} is printed as {}", s, r);
+
+        r.to_string()
+    }
+
+    #[test]
+    fn test_to_regex() {
+        assert_eq!(to_regex(r#"abc"#), "abc");
+        assert_eq!(to_regex(r#"a|b|c"#), "[a-c]");
+        assert_eq!(to_regex(r#"a(b|c)*d"#), "a[bc]*d");
+        assert_eq!(to_regex(r#"(ab)+"#), "(?:ab)+");
+        assert_eq!(to_regex(r#"x?"#), "x?");
+        assert_eq!(to_regex(r#"[^a]"#), "[^a]");
+        assert_eq!(to_regex(r#"\d+"#), "\\d+");
+        assert_eq!(to_regex(r#"."#), ".");
+        assert_eq!(to_regex(r#"a\bb"#), "[^\\0-\u{10ffff}]");
+
+        for s in &[
+            r#"(a|b)*abb"#,
+            r#"colou?r|colour"#,
+            r#"(foo|bar)+(\.\d{2})?"#,
+            r#"((a|b)(c|d))*e?"#,
+            r#"[a-z]{2,4}"#,
+            r#"(ab|ba)*|c"#,
+            r#""#,
+        ] {
+            to_regex(s);
+        }
+
+        // strings of an identifier but not a keyword
+        let t = dfa(r#"[a-z]+"#).difference(&dfa(r#"if|in"#));
+        let r = t.to_regex().unwrap();
+        let ident: RegexItem = r#"[a-z]+"#.into();
+        let keyword: RegexItem = r#"if|in"#.into();
+        assert!(r.compare(&ident).is_included());
+        let c = ident.compare(&r);
+        assert_eq!(c.only_lhs(), Some("if"));
+        assert!(r.compare(&keyword).only_rhs().is_some());
+    }
+
+    #[test]
+    fn test_to_regex_limit() {
+        let mut t = dfa(r#"[a-z]{2,4}(\.[a-z]{2,4})*"#);
+        t.minimize();
+        assert!(t.to_regex().is_some());
+
+        // the whole expression is the largest one during elimination
+        let limit = size(&t.eliminate(usize::MAX).unwrap());
+        assert!(t.to_regex_with_limit(limit).is_some());
+        assert!(t.to_regex_with_limit(limit - 1).is_none());
+    }
+}
@@ -1,32 +1,261 @@
 
+use std::collections::HashSet;
+
+use node::States;
+use regex_gen::RegexItem;
 use transtable::TransTable;
 
 pub struct ExecuteEngine {
     transtable: TransTable,
+
+    // the nfa keeps the edges which save positions of groups, it is only
+    // used to place the groups after the match is found by `transtable`.
+    nfa: Option<TransTable>,
+    capture_names: Vec<Option<String>>,
 }
 
 impl ExecuteEngine {
+    /// build the dfa, and the nfa needed by `captures`
+    pub fn new(item: &RegexItem) -> ExecuteEngine {
+        let mut transtable = TransTable::from_nfa(&item.nfa_graph());
+        transtable.as_dfa();
+
+        ExecuteEngine {
+            transtable,
+            nfa: Some(TransTable::from_nfa(&item.nfa_graph())),
+            capture_names: item.capture_names(),
+        }
+    }
+
+    /// only the whole match is captured by an engine without the nfa
     pub fn with_transtable(transtable: TransTable) -> ExecuteEngine {
         ExecuteEngine {
             transtable,
+            nfa: None,
+            capture_names: vec![None],
         }
     }
 
     pub fn exact_match<T: AsRef<str>>(&self, s: T) -> bool {
-        let mut s = s.as_ref().chars();
-        let mut state = self.transtable.start_id();
+        self.transtable.accepts(s.as_ref())
+    }
+
+    /// if any substring of `s` matches
+    pub fn is_match<T: AsRef<str>>(&self, s: T) -> bool {
+        self.search(s.as_ref(), 0, true).is_some()
+    }
+
+    /// byte range of the leftmost-longest match: the match which starts first wins,
+    /// and the longest one is chosen between matches starting at the same position.
+    pub fn find<T: AsRef<str>>(&self, s: T) -> Option<(usize, usize)> {
+        self.find_at(s, 0)
+    }
+
+    /// same as `find`, but the match starts at or after the byte offset `start`.
+    /// the text before `start` is still seen by assertions such as `^` and `\b`.
+    /// returns `None` if `start` is past the end or not on a char boundary.
+    pub fn find_at<T: AsRef<str>>(&self, s: T, start: usize) -> Option<(usize, usize)> {
+        let text = s.as_ref();
+        if !text.is_char_boundary(start) {
+            return None;
+        }
+
+        self.search(text, start, false)
+    }
+
+    /// the match found by `find` and the spans of its groups. if a group could
+    /// match in several ways, the first alternative and the longest repetition wins.
+    pub fn captures<T: AsRef<str>>(&self, s: T) -> Option<Captures<'_>> {
+        self.captures_at(s, 0)
+    }
 
-        while let Some(c) = s.next() {
-            let ref trans = self.transtable.trans_map().get(state).unwrap();
+    pub fn captures_at<T: AsRef<str>>(&self, s: T, start: usize) -> Option<Captures<'_>> {
+        let text = s.as_ref();
+        let (start, end) = self.find_at(text, start)?;
 
-            if let Some(e) = trans.iter().find(|x| x.match_character(c as u8)) {
-                state = &e.next_node();
-            } else {
-                return false;
+        let mut spans = vec![None; self.capture_names.len()];
+        spans[0] = Some((start, end));
+
+        if let Some(slots) = self.nfa.as_ref().and_then(|x| capture_slots(x, text, start, end, spans.len() * 2)) {
+            for (i, span) in spans.iter_mut().enumerate().skip(1) {
+                if let (Some(s), Some(e)) = (slots[i * 2], slots[i * 2 + 1]) {
+                    *span = Some((s, e));
+                }
             }
         }
 
-        s.next().is_none() && self.transtable.end_set().contains(state)
+        Some(Captures {
+            names: &self.capture_names,
+            spans,
+        })
+    }
+
+    /// successive non-overlapping matches, an empty match right after another match is skipped
+    pub fn find_iter<'e, 't>(&'e self, text: &'t str) -> Matches<'e, 't> {
+        Matches {
+            engine: self,
+            text,
+            last_end: 0,
+            last_match: None,
+        }
+    }
+
+    // run the table from every position at once in a single pass. at most one thread
+    // is kept for each state, the earliest started one, because they share the same future.
+    fn search(&self, text: &str, start: usize, earliest: bool) -> Option<(usize, usize)> {
+        let table = &self.transtable;
+        let mut threads: Vec<(usize, &States)> = vec![];
+        let mut best: Option<(usize, usize)> = None;
+        let mut prev = text[..start].chars().next_back();
+        let mut pos = start;
+
+        loop {
+            let next = text[pos..].chars().next();
+
+            // no need to start new threads if there is a match already
+            if best.is_none() {
+                let state = table.start_after(prev);
+                if threads.iter().all(|&(_, x)| x != state) {
+                    threads.push((pos, state));
+                }
+            }
+
+            for &(from, state) in &threads {
+                if table.is_end_before(state, next) && best.is_none_or(|(s, _)| from <= s) {
+                    best = Some((from, pos));
+                }
+            }
+
+            if let Some((s, _)) = best {
+                if earliest {
+                    return best;
+                }
+                threads.retain(|&(from, _)| from <= s);
+            }
+
+            let c = match next {
+                Some(c) if !threads.is_empty() || best.is_none() => c,
+                _ => return best,
+            };
+
+            let mut seen = HashSet::new();
+            threads = threads
+                .into_iter()
+                .filter_map(|(from, state)| Some((from, table.next_state(state, c)?)))
+                .filter(|&(_, state)| seen.insert(state))
+                .collect();
+
+            prev = Some(c);
+            pos += c.len_utf8();
+        }
+    }
+}
+
+type Slots = Vec<Option<usize>>;
+
+// simulate the nfa over `text[start..end]` and keep the slots of the thread with the
+// highest priority, which are saved by edges into and out of the capture groups.
+fn capture_slots(nfa: &TransTable, text: &str, start: usize, end: usize, slots: usize) -> Option<Slots> {
+    let mut threads = vec![];
+    let first = nfa.start_after(text[..start].chars().next_back());
+    add_thread(nfa, &mut threads, first, vec![None; slots], start);
+
+    let mut pos = start;
+    for c in text[start..end].chars() {
+        let mut next = vec![];
+        pos += c.len_utf8();
+
+        for (state, slots) in &threads {
+            for e in nfa.trans_map()[*state].iter().filter(|x| x.match_character(c)) {
+                add_thread(nfa, &mut next, e.next_node(), slots.clone(), pos);
+            }
+        }
+
+        threads = next;
+    }
+
+    let next = text[end..].chars().next();
+    threads
+        .into_iter()
+        .find(|&(state, _)| nfa.is_end_before(state, next))
+        .map(|(_, slots)| slots)
+}
+
+// append `state` and the states after it through epsilon edges in order of priority,
+// a state which is in the list already belongs to a thread with higher priority.
+fn add_thread<'t>(nfa: &'t TransTable, list: &mut Vec<(&'t States, Slots)>, state: &'t States, slots: Slots, pos: usize) {
+    let mut stack = vec![(state, slots)];
+
+    while let Some((state, slots)) = stack.pop() {
+        if list.iter().any(|&(x, _)| x == state) {
+            continue;
+        }
+
+        for e in nfa.trans_map()[state].iter().rev().filter(|x| x.matches().is_none()) {
+            let mut slots = slots.clone();
+            if let Some(i) = e.slot() {
+                slots[i] = Some(pos);
+            }
+
+            stack.push((e.next_node(), slots));
+        }
+
+        list.push((state, slots));
+    }
+}
+
+#[derive(Debug)]
+pub struct Captures<'e> {
+    names: &'e [Option<String>],
+    spans: Vec<Option<(usize, usize)>>,
+}
+
+impl<'e> Captures<'e> {
+    /// byte range of group `i`, group 0 is the whole match
+    pub fn get(&self, i: usize) -> Option<(usize, usize)> {
+        self.spans.get(i).cloned().unwrap_or(None)
+    }
+
+    pub fn name(&self, name: &str) -> Option<(usize, usize)> {
+        let i = self.names.iter().position(|x| x.as_ref().is_some_and(|x| x == name))?;
+        self.get(i)
+    }
+
+    /// spans of all groups by index, `None` for groups which did not take part in the match
+    pub fn spans(&self) -> &[Option<(usize, usize)>] {
+        &self.spans
+    }
+}
+
+pub struct Matches<'e, 't> {
+    engine: &'e ExecuteEngine,
+    text: &'t str,
+    last_end: usize,
+    last_match: Option<usize>,
+}
+
+impl<'e, 't> Iterator for Matches<'e, 't> {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<(usize, usize)> {
+        if self.last_end > self.text.len() {
+            return None;
+        }
+
+        let (s, e) = self.engine.find_at(self.text, self.last_end)?;
+        if s == e {
+            // step over the next character, or over the end of text
+            self.last_end = e + self.text[e..].chars().next().map_or(1, |c| c.len_utf8());
+
+            if Some(e) == self.last_match {
+                return self.next();
+            }
+        } else {
+            self.last_end = e;
+        }
+
+        self.last_match = Some(e);
+        Some((s, e))
     }
 }
 
@@ -36,6 +265,10 @@ mod test {
     use execute_engine::*;
     use regex_gen::*;
 
+    fn ee(s: &str) -> ExecuteEngine {
+        ExecuteEngine::with_transtable(dfa(s))
+    }
+
     #[test]
     fn test_execute_not() {
         let r: RegexItem = r#"[^\dab]+"#.into();
@@ -93,5 +326,206 @@ mod test {
         assert_eq!(ee.exact_match("bb"), false);
         assert_eq!(ee.exact_match("c"), false);
     }
-}
 
+    #[test]
+    fn test_execute_overlapped() {
+        let r: RegexItem = r#"(a|ab)c"#.into();
+        let mut t = TransTable::from_nfa(&r.nfa_graph());
+        t.as_dfa();
+
+        let ee = ExecuteEngine::with_transtable(t);
+        assert!(ee.exact_match("ac"));
+        assert!(ee.exact_match("abc"));
+        assert!(!ee.exact_match("ab"));
+
+        let r: RegexItem = r#"(c|[a-z])+|\d"#.into();
+        let mut t = TransTable::from_nfa(&r.nfa_graph());
+        t.as_dfa();
+
+        let ee = ExecuteEngine::with_transtable(t);
+        assert!(ee.exact_match("c"));
+        assert!(ee.exact_match("abc"));
+        assert!(ee.exact_match("cca"));
+        assert!(ee.exact_match("5"));
+        assert!(!ee.exact_match("c5"));
+
+        let r: RegexItem = r#"[^b]*b|.c"#.into();
+        let mut t = TransTable::from_nfa(&r.nfa_graph());
+        t.as_dfa();
+
+        let ee = ExecuteEngine::with_transtable(t);
+        assert!(ee.exact_match("aab"));
+        assert!(ee.exact_match("bc"));
+        assert!(ee.exact_match("ac"));
+        assert!(!ee.exact_match("bb"));
+        assert_eq!(ee.find("xxbc"), Some((0, 3)));
+    }
+
+    #[test]
+    fn test_execute_assertion() {
+        let e = ee(r#"^ab$"#);
+        assert!(e.exact_match("ab"));
+        assert!(!e.exact_match("a"));
+        assert!(!ee(r#"a^b"#).exact_match("ab"));
+        assert!(!ee(r#"a$b"#).exact_match("ab"));
+        assert!(!ee(r#"a$\nb"#).exact_match("a\nb"));
+
+        let e = ee(r#"(?m)a$\n^b"#);
+        assert!(e.exact_match("a\nb"));
+        assert!(!e.exact_match("a\n"));
+
+        let e = ee(r#"\w+\b.*"#);
+        assert!(e.exact_match("foo"));
+        assert!(e.exact_match("foo bar"));
+        assert!(e.exact_match("foo-bar"));
+
+        let e = ee(r#"\bfoo\b"#);
+        assert!(e.exact_match("foo"));
+        assert!(!e.exact_match("foox"));
+
+        let e = ee(r#"a\B.*"#);
+        assert!(e.exact_match("ab"));
+        assert!(e.exact_match("a1"));
+        assert!(!e.exact_match("a"));
+        assert!(!e.exact_match("a b"));
+        assert!(!e.exact_match("a\n"));
+
+        let e = ee(r#"x\b[^a-z]"#);
+        assert!(e.exact_match("x "));
+        assert!(e.exact_match("x日"));
+        assert!(!e.exact_match("xA"));
+    }
+
+    #[test]
+    fn test_find() {
+        let e = ee(r#"a\d+b"#);
+        assert!(e.is_match("xxa12bxx"));
+        assert!(!e.is_match("xxa12xx"));
+        assert_eq!(e.find("xxa12bxx"), Some((2, 6)));
+        assert_eq!(e.find("a1a12b"), Some((2, 6)));
+        assert_eq!(e.find("ab"), None);
+        assert_eq!(e.find_at("a1ba2b", 1), Some((3, 6)));
+
+        // leftmost first, then longest
+        let e = ee(r#"b+|ab"#);
+        assert_eq!(e.find("xabbb"), Some((1, 3)));
+        let e = ee(r#"a*"#);
+        assert_eq!(e.find("baa"), Some((0, 0)));
+        assert_eq!(e.find_at("baa", 1), Some((1, 3)));
+
+        let e = ee(r#"é+"#);
+        assert_eq!(e.find("日éé本"), Some((3, 7)));
+        assert_eq!(e.find_at("日éé本", 5), Some((5, 7)));
+        assert_eq!(e.find_at("日éé本", 4), None);
+        assert_eq!(e.find_at("日éé本", 11), None);
+    }
+
+    #[test]
+    fn test_find_iter() {
+        let e = ee(r#"\d+"#);
+        let m: Vec<(usize, usize)> = e.find_iter("a1b22c333").collect();
+        assert_eq!(m, vec![(1, 2), (3, 5), (6, 9)]);
+        assert_eq!(e.find_iter("abc").count(), 0);
+
+        let e = ee(r#"a*"#);
+        let m: Vec<(usize, usize)> = e.find_iter("baab").collect();
+        assert_eq!(m, vec![(0, 0), (1, 3), (4, 4)]);
+        let m: Vec<(usize, usize)> = e.find_iter("é").collect();
+        assert_eq!(m, vec![(0, 0), (2, 2)]);
+    }
+
+    #[test]
+    fn test_find_assertion() {
+        let e = ee(r#"\bfoo\b"#);
+        assert_eq!(e.find("a foo b"), Some((2, 5)));
+        assert_eq!(e.find("foobar foo"), Some((7, 10)));
+        assert!(!e.is_match("xfoo foox"));
+        assert_eq!(e.find_at("foo", 1), None);
+
+        let e = ee(r#"^ab"#);
+        assert_eq!(e.find("abab"), Some((0, 2)));
+        assert_eq!(e.find_at("abab", 2), None);
+        assert!(!e.is_match("xab"));
+
+        let e = ee(r#"\d+$"#);
+        assert_eq!(e.find("1 22\n333"), Some((5, 8)));
+
+        let e = ee(r#"(?m)^\d+$"#);
+        let m: Vec<(usize, usize)> = e.find_iter("1 22\n333\n4").collect();
+        assert_eq!(m, vec![(5, 8), (9, 10)]);
+
+        let e = ee(r#"\B"#);
+        let m: Vec<(usize, usize)> = e.find_iter("ab c").collect();
+        assert_eq!(m, vec![(1, 1)]);
+    }
+
+    #[test]
+    fn test_captures() {
+        let r: RegexItem = r#"(?P<year>\d{4})-(?P<month>\d\d)-(\d\d)"#.into();
+        let ee = ExecuteEngine::new(&r);
+
+        let text = "date: 2024-01-31.";
+        let caps = ee.captures(text).unwrap();
+        assert_eq!(caps.get(0), Some((6, 16)));
+        assert_eq!(caps.name("year"), Some((6, 10)));
+        assert_eq!(caps.name("month"), Some((11, 13)));
+        assert_eq!(caps.get(3), Some((14, 16)));
+        assert_eq!(caps.get(4), None);
+        assert_eq!(caps.name("day"), None);
+        assert!(ee.captures("2024-1-31").is_none());
+
+        // groups which do not take part in the match
+        let r: RegexItem = r#"(a)|(b)"#.into();
+        let ee = ExecuteEngine::new(&r);
+        let caps = ee.captures("xb").unwrap();
+        assert_eq!(caps.spans(), &[Some((1, 2)), None, Some((1, 2))][..]);
+
+        // the last iteration of a repeated group
+        let r: RegexItem = r#"(?:(\w)\s*)+"#.into();
+        let ee = ExecuteEngine::new(&r);
+        let caps = ee.captures("a b c").unwrap();
+        assert_eq!(caps.get(0), Some((0, 5)));
+        assert_eq!(caps.get(1), Some((4, 5)));
+
+        // greedy repetition inside of the match found by `find`
+        let r: RegexItem = r#"(a*)(a*)b"#.into();
+        let ee = ExecuteEngine::new(&r);
+        let caps = ee.captures("aab").unwrap();
+        assert_eq!(caps.spans(), &[Some((0, 3)), Some((0, 2)), Some((2, 2))][..]);
+
+        let r: RegexItem = r#"\b(\w+)$"#.into();
+        let ee = ExecuteEngine::new(&r);
+        assert_eq!(ee.captures("foo bar").unwrap().get(1), Some((4, 7)));
+        assert!(ee.captures_at("foo bar", 5).is_none());
+
+        let mut t = TransTable::from_nfa(&r.nfa_graph());
+        t.as_dfa();
+        let ee = ExecuteEngine::with_transtable(t);
+        let caps = ee.captures("foo bar").unwrap();
+        assert_eq!(caps.spans(), &[Some((4, 7))][..]);
+    }
+
+    #[test]
+    fn test_execute_unicode() {
+        let r: RegexItem = r#"[α-ω]+\s.é"#.into();
+        let mut t = TransTable::from_nfa(&r.nfa_graph());
+        t.as_dfa();
+
+        let ee = ExecuteEngine::with_transtable(t);
+        assert!(ee.exact_match("λογος 日é"));
+        assert!(ee.exact_match("αω\t😀é"));
+        assert!(!ee.exact_match("ΛΟΓΟΣ 日é"));
+        assert!(!ee.exact_match("abc 日é"));
+        assert!(!ee.exact_match("α 日e"));
+        assert!(!ee.exact_match("α \né"));
+
+        let r: RegexItem = r#"[^é\u{1F600}]+"#.into();
+        let mut t = TransTable::from_nfa(&r.nfa_graph());
+        t.as_dfa();
+
+        let ee = ExecuteEngine::with_transtable(t);
+        assert!(ee.exact_match("e日本語"));
+        assert!(!ee.exact_match("日本é"));
+        assert!(!ee.exact_match("😀"));
+    }
+}
@@ -0,0 +1,577 @@
+use std::collections::HashMap;
+
+use node::*;
+use transtable::TransTable;
+
+const DEFAULT_MAX_LENGTH: usize = 32;
+// classes outside of the alphabet with more characters are represented by one of them in `Shortlex`
+const EXPAND_LIMIT: u32 = 10;
+
+// xorshift64*, good enough for test data and saves a dependency on `rand`
+struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        // splitmix64 step, so near seeds give unrelated streams and the state is never 0
+        let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+
+        Rng {
+            state: (z ^ (z >> 31)) | 1,
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    // uniform in `0..n`, `n` must not be 0
+    fn below(&mut self, n: u64) -> u64 {
+        ((self.next_u64() as u128 * n as u128) >> 64) as u64
+    }
+
+    // uniform in `[0, 1)`
+    fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn chance(&mut self, p: f64) -> bool {
+        self.unit() < p
+    }
+}
+
+pub struct GeneratorBuilder {
+    seed: u64,
+    min_length: usize,
+    max_length: usize,
+    repeat: f64,
+    alphabet: Vec<(char, char)>,
+}
+
+impl Default for GeneratorBuilder {
+    fn default() -> GeneratorBuilder {
+        GeneratorBuilder::new()
+    }
+}
+
+impl GeneratorBuilder {
+    pub fn new() -> GeneratorBuilder {
+        GeneratorBuilder {
+            seed: 0,
+            min_length: 0,
+            max_length: DEFAULT_MAX_LENGTH,
+            repeat: 0.5,
+            alphabet: vec![(' ', '~')],
+        }
+    }
+
+    /// the same seed generates the same strings
+    pub fn seed(&mut self, seed: u64) -> &mut GeneratorBuilder {
+        self.seed = seed;
+        self
+    }
+
+    /// length of generated strings in characters
+    pub fn min_length(&mut self, len: usize) -> &mut GeneratorBuilder {
+        self.min_length = len;
+        self
+    }
+
+    pub fn max_length(&mut self, len: usize) -> &mut GeneratorBuilder {
+        self.max_length = len;
+        self
+    }
+
+    /// chance to go around a loop, such as `*` and `+`, once more when it may be left
+    pub fn repeat(&mut self, p: f64) -> &mut GeneratorBuilder {
+        self.repeat = p;
+        self
+    }
+
+    /// characters drawn for wide classes like `.` and `[^...]`, printable ascii by default.
+    /// characters of a class outside of the alphabet are only used if it has none inside.
+    pub fn alphabet(&mut self, ranges: &[(char, char)]) -> &mut GeneratorBuilder {
+        self.alphabet = normalize_ranges(ranges.to_vec());
+        self
+    }
+
+    /// the table must be a dfa, see `TransTable::as_dfa`
+    pub fn build(&self, table: &TransTable) -> Generator {
+        let (start, end, edges) = self.graph(table);
+
+        let mut r = Generator {
+            rng: Rng::new(self.seed),
+            min_length: self.min_length,
+            max_length: self.max_length,
+            repeat: self.repeat,
+            reach: reach_table(&end, &edges, self.max_length),
+            counts: vec![],
+            start,
+            end,
+            edges,
+        };
+        r.mark_repeat_edges();
+
+        r
+    }
+
+    /// all accepted strings between the length bounds, in shortlex order. a class gives each
+    /// of its characters in the alphabet, a wide class outside of the alphabet gives one of them.
+    pub fn shortlex(&self, table: &TransTable) -> Shortlex {
+        let (start, end, edges) = self.graph(table);
+
+        let options = edges
+            .iter()
+            .map(|edges| {
+                let mut r: Vec<(char, usize)> = edges
+                    .iter()
+                    .flat_map(|e| {
+                        // edges keep their characters in the alphabet if they have any
+                        let inside = !intersect_ranges(&e.ranges, &self.alphabet).is_empty();
+                        let len = ranges_len(&e.ranges);
+                        let chars: Vec<char> = if inside || len <= EXPAND_LIMIT {
+                            (0..len).filter_map(|n| nth_char(&e.ranges, n)).collect()
+                        } else {
+                            vec![e.ranges[0].0]
+                        };
+
+                        chars.into_iter().map(move |c| (c, e.dest))
+                    })
+                    .collect();
+                r.sort();
+                r
+            })
+            .collect();
+
+        Shortlex {
+            max_length: self.max_length,
+            reach: reach_table(&end, &edges, self.max_length),
+            start,
+            options,
+            length: self.min_length,
+            started: false,
+            stack: vec![],
+            chars: vec![],
+        }
+    }
+
+    // states of the table numbered in order, edges keep characters of the alphabet if they have any
+    fn graph(&self, table: &TransTable) -> (usize, Vec<bool>, Vec<Vec<GeneratorEdge>>) {
+        let mut states: Vec<&States> = table.trans_map().keys().collect();
+        states.sort();
+        let index: HashMap<&States, usize> = states.iter().enumerate().map(|(i, &x)| (x, i)).collect();
+
+        let edges: Vec<Vec<GeneratorEdge>> = states
+            .iter()
+            .map(|&state| {
+                table.trans_map()[state]
+                    .iter()
+                    .filter_map(|e| {
+                        let ranges = e.matches().as_ref()?.ranges();
+                        let inside = intersect_ranges(&ranges, &self.alphabet);
+
+                        Some(GeneratorEdge {
+                            ranges: if inside.is_empty() { ranges } else { inside },
+                            dest: index[e.next_node()],
+                            repeat: false,
+                        })
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let end = states.iter().map(|&x| table.end_set().contains(x)).collect();
+
+        (index[table.start_id()], end, edges)
+    }
+}
+
+// `reach[k][q]` tells if an end can be reached from `q` by `k` characters
+fn reach_table(end: &[bool], edges: &[Vec<GeneratorEdge>], max_length: usize) -> Vec<Vec<bool>> {
+    let mut reach = vec![end.to_vec()];
+
+    for k in 1..=max_length {
+        let next = edges.iter().map(|edges| edges.iter().any(|e| reach[k - 1][e.dest])).collect();
+        reach.push(next);
+    }
+
+    reach
+}
+
+struct GeneratorEdge {
+    ranges: Vec<(char, char)>,
+    dest: usize,
+    // the edge is part of a loop
+    repeat: bool,
+}
+
+/// random strings accepted by a dfa, see `GeneratorBuilder`
+pub struct Generator {
+    rng: Rng,
+    min_length: usize,
+    max_length: usize,
+    repeat: f64,
+
+    start: usize,
+    end: Vec<bool>,
+    edges: Vec<Vec<GeneratorEdge>>,
+    reach: Vec<Vec<bool>>,
+
+    // `counts[k][q]` is in proportion to the number of strings of `k` characters which lead
+    // from `q` to an end, rows are scaled to keep them in range of f64.
+    counts: Vec<Vec<f64>>,
+}
+
+impl Generator {
+    /// `None` if no accepted string has a length between the bounds
+    pub fn generate(&mut self) -> Option<String> {
+        if !self.fits(self.start, 0) {
+            return None;
+        }
+
+        let mut r = String::new();
+        let mut state = self.start;
+        let mut len = 0;
+
+        loop {
+            // `None` stands for stopping at the current state
+            let mut looping = vec![];
+            let mut leaving = vec![];
+            if self.end[state] && len >= self.min_length {
+                leaving.push(None);
+            }
+            for (i, e) in self.edges[state].iter().enumerate() {
+                if self.fits(e.dest, len + 1) {
+                    if e.repeat {
+                        looping.push(Some(i));
+                    } else {
+                        leaving.push(Some(i));
+                    }
+                }
+            }
+
+            let choices = if leaving.is_empty() || (!looping.is_empty() && self.rng.chance(self.repeat)) {
+                looping
+            } else {
+                leaving
+            };
+
+            let e = match choices[self.rng.below(choices.len() as u64) as usize] {
+                Some(i) => &self.edges[state][i],
+                None => return Some(r),
+            };
+
+            let n = self.rng.below(ranges_len(&e.ranges) as u64) as u32;
+            r.extend(nth_char(&e.ranges, n));
+            state = e.dest;
+            len += 1;
+        }
+    }
+
+    /// a string of `n` characters, regardless of the length bounds. `None` if no accepted
+    /// string has `n` characters.
+    ///
+    /// the draw is uniform over the strings made of the characters `generate` would use:
+    /// an edge only keeps its characters inside the alphabet, or all of them if none is
+    /// inside. so it is not uniform over all accepted strings, `.` never gives `é` with the
+    /// default alphabet. the weights are `f64`, very large counts are only approximated.
+    pub fn sample(&mut self, n: usize) -> Option<String> {
+        self.fill_counts(n);
+        if self.counts[n][self.start] == 0.0 {
+            return None;
+        }
+
+        let mut r = String::new();
+        let mut state = self.start;
+        for k in (0..n).rev() {
+            let weights: Vec<f64> = self.edges[state]
+                .iter()
+                .map(|e| ranges_len(&e.ranges) as f64 * self.counts[k][e.dest])
+                .collect();
+
+            // the last possible edge takes what rounding errors leave
+            let mut x = self.rng.unit() * weights.iter().sum::<f64>();
+            let mut i = weights.iter().rposition(|&w| w > 0.0).unwrap();
+            for (j, &w) in weights.iter().enumerate() {
+                if x < w {
+                    i = j;
+                    break;
+                }
+                x -= w;
+            }
+
+            let e = &self.edges[state][i];
+            let n = self.rng.below(ranges_len(&e.ranges) as u64) as u32;
+            r.extend(nth_char(&e.ranges, n));
+            state = e.dest;
+        }
+
+        Some(r)
+    }
+
+    fn fill_counts(&mut self, n: usize) {
+        if self.counts.is_empty() {
+            self.counts.push(self.end.iter().map(|&x| if x { 1.0 } else { 0.0 }).collect());
+        }
+
+        while self.counts.len() <= n {
+            let last = self.counts.last().unwrap();
+            let mut next: Vec<f64> = self
+                .edges
+                .iter()
+                .map(|edges| edges.iter().map(|e| ranges_len(&e.ranges) as f64 * last[e.dest]).sum())
+                .collect();
+
+            let max = next.iter().cloned().fold(0.0, f64::max);
+            if max > 0.0 {
+                for x in &mut next {
+                    *x /= max;
+                }
+            }
+            self.counts.push(next);
+        }
+    }
+
+    // if an accepted string with a length between the bounds goes through `state` after `len` characters
+    fn fits(&self, state: usize, len: usize) -> bool {
+        if len > self.max_length {
+            return false;
+        }
+
+        (self.min_length.saturating_sub(len)..=self.max_length - len).any(|k| self.reach[k][state])
+    }
+
+    // an edge is part of a loop if its source can be reached again from its destination
+    fn mark_repeat_edges(&mut self) {
+        let reachable: Vec<Vec<bool>> = (0..self.edges.len())
+            .map(|q| {
+                let mut visited = vec![false; self.edges.len()];
+                let mut visit = vec![q];
+                while let Some(x) = visit.pop() {
+                    for e in &self.edges[x] {
+                        if !visited[e.dest] {
+                            visited[e.dest] = true;
+                            visit.push(e.dest);
+                        }
+                    }
+                }
+
+                visited
+            })
+            .collect();
+
+        for (q, edges) in self.edges.iter_mut().enumerate() {
+            for e in edges {
+                e.repeat = reachable[e.dest][q];
+            }
+        }
+    }
+}
+
+/// iterator of `GeneratorBuilder::shortlex`
+pub struct Shortlex {
+    max_length: usize,
+    reach: Vec<Vec<bool>>,
+    start: usize,
+    // characters and destinations of every state, sorted by the characters
+    options: Vec<Vec<(char, usize)>>,
+
+    // strings of `length` are walked depth first, a frame of `stack` is a state and
+    // the next option to try, `chars` leads to the state of the last frame.
+    length: usize,
+    started: bool,
+    stack: Vec<(usize, usize)>,
+    chars: Vec<char>,
+}
+
+impl Iterator for Shortlex {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        loop {
+            let (state, i) = match self.stack.last() {
+                Some(&x) => x,
+                None if self.started => {
+                    // all strings of this length are done
+                    self.started = false;
+                    self.length += 1;
+                    continue;
+                }
+                None => {
+                    if self.length > self.max_length {
+                        return None;
+                    }
+
+                    self.started = true;
+                    if self.reach[self.length][self.start] {
+                        self.stack.push((self.start, 0));
+                    }
+                    continue;
+                }
+            };
+            let depth = self.stack.len() - 1;
+
+            if depth == self.length {
+                let r = self.chars.iter().collect();
+                self.stack.pop();
+                self.chars.pop();
+                return Some(r);
+            }
+
+            match self.options[state].get(i) {
+                Some(&(c, dest)) => {
+                    self.stack.last_mut().unwrap().1 += 1;
+                    if self.reach[self.length - depth - 1][dest] {
+                        self.chars.push(c);
+                        self.stack.push((dest, 0));
+                    }
+                }
+                None => {
+                    self.stack.pop();
+                    self.chars.pop();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use execute_engine::*;
+    use generator::*;
+    use regex_gen::*;
+    use transtable::dfa;
+
+    #[test]
+    fn test_generate() {
+        for s in &[
+            r#"a(b|c)*d"#,
+            r#"[a-z]{3,5}@[a-z]+\.(com|org)"#,
+            r#"\d+(\.\d+)?"#,
+            r#"(?i)hello[^x]?"#,
+            r#"\bfoo\b.*"#,
+            r#"x*"#,
+        ] {
+            let r: RegexItem = (*s).into();
+            let e = ExecuteEngine::new(&r);
+            let mut g = GeneratorBuilder::new().seed(7).build(&dfa(s));
+
+            for _ in 0..50 {
+                let x = g.generate().unwrap();
+                assert!(e.exact_match(&x), "{} doesn't match {}", x, s);
+                assert!(x.chars().count() <= DEFAULT_MAX_LENGTH);
+            }
+        }
+    }
+
+    #[test]
+    fn test_generate_options() {
+        let t = dfa(r#"a+"#);
+
+        let mut g = GeneratorBuilder::new().seed(1).build(&t);
+        let mut h = GeneratorBuilder::new().seed(1).build(&t);
+        for _ in 0..10 {
+            assert_eq!(g.generate(), h.generate());
+        }
+
+        let mut g = GeneratorBuilder::new().min_length(4).max_length(6).build(&t);
+        for _ in 0..50 {
+            let len = g.generate().unwrap().len();
+            assert!((4..=6).contains(&len));
+        }
+
+        assert_eq!(GeneratorBuilder::new().min_length(3).build(&dfa(r#"ab"#)).generate(), None);
+        assert_eq!(GeneratorBuilder::new().max_length(1).build(&dfa(r#"ab"#)).generate(), None);
+        assert_eq!(GeneratorBuilder::new().build(&dfa(r#"a\bb"#)).generate(), None);
+
+        let total = |p: f64| -> usize {
+            let mut g = GeneratorBuilder::new().repeat(p).build(&t);
+            (0..100).map(|_| g.generate().unwrap().len()).sum()
+        };
+        assert!(total(0.9) > total(0.1) * 3);
+        assert_eq!(total(0.0), 100);
+
+        let mut g = GeneratorBuilder::new().alphabet(&[('0', '1')]).build(&dfa(r#"[^a]{8}|é"#));
+        for _ in 0..50 {
+            let x = g.generate().unwrap();
+            assert!(x.chars().all(|c| c == '0' || c == '1' || c == 'é'));
+        }
+    }
+
+    #[test]
+    fn test_shortlex() {
+        let strings = |s: &str, max_length: usize| -> Vec<String> {
+            GeneratorBuilder::new().max_length(max_length).shortlex(&dfa(s)).collect()
+        };
+
+        assert_eq!(strings(r#"b|a|ab|ba|aa"#, 8), vec!["a", "b", "aa", "ab", "ba"]);
+        assert_eq!(strings(r#"[ab]*"#, 2), vec!["", "a", "b", "aa", "ab", "ba", "bb"]);
+        assert_eq!(strings(r#"x\d?"#, 8).len(), 11);
+        // `a` and `[^a]` lead to the same state after `a`, they are one class
+        let r = strings(r#"a[^a]|.a"#, 8);
+        assert_eq!(r.len(), 94 + 95);
+        assert_eq!(r[..2], [" a", "!a"]);
+        assert!(r.contains(&"aa".to_string()));
+        assert!(r.contains(&"a ".to_string()));
+        let r = strings(r#"[a-z]{2}"#, 8);
+        assert_eq!(r.len(), 26 * 26);
+        assert_eq!(r[..3], ["aa", "ab", "ac"]);
+        assert_eq!(r.last().unwrap(), "zz");
+        let r = strings(r#"cat|[a-z]at"#, 8);
+        assert_eq!(r.len(), 26);
+        assert_eq!(r[..3], ["aat", "bat", "cat"]);
+        assert_eq!(strings(r#"\bfoo\b"#, 8), vec!["foo"]);
+        assert_eq!(strings(r#"a\bb"#, 8), Vec::<String>::new());
+
+        let r: Vec<String> = GeneratorBuilder::new().min_length(2).shortlex(&dfa(r#"(a|b)+"#)).take(3).collect();
+        assert_eq!(r, vec!["aa", "ab", "ba"]);
+
+        let r: Vec<String> = GeneratorBuilder::new().alphabet(&[('x', 'z')]).shortlex(&dfa(r#"[^a]"#)).collect();
+        assert_eq!(r, vec!["x", "y", "z"]);
+
+        assert_eq!(GeneratorBuilder::new().shortlex(&dfa(r#"(ab)*"#)).count(), DEFAULT_MAX_LENGTH / 2 + 1);
+    }
+
+    #[test]
+    fn test_sample() {
+        let mut g = GeneratorBuilder::new().seed(3).build(&dfa(r#"(a|bcdefgh)+"#));
+        let mut counts = HashMap::new();
+        for _ in 0..3000 {
+            *counts.entry(g.sample(8).unwrap()).or_insert(0) += 1;
+        }
+
+        // "aaaaaaaa", "abcdefgh" and "bcdefgha"
+        assert_eq!(counts.len(), 3);
+        assert!(counts.values().all(|&x| 850 < x && x < 1150), "{:?}", counts);
+
+        let r: RegexItem = r#"[ab]{3}|x\d*"#.into();
+        let e = ExecuteEngine::new(&r);
+        let mut g = GeneratorBuilder::new().build(&dfa(r#"[ab]{3}|x\d*"#));
+        let mut counts = HashMap::new();
+        for _ in 0..1800 {
+            let x = g.sample(3).unwrap();
+            assert!(e.exact_match(&x));
+            *counts.entry(x).or_insert(0) += 1;
+        }
+        assert_eq!(counts.len(), 108);
+
+        assert!(g.sample(200).unwrap().starts_with('x'));
+
+        // characters outside the alphabet are never drawn if an edge has some inside
+        let mut g = GeneratorBuilder::new().build(&dfa(r#".|é"#));
+        for _ in 0..100 {
+            let x = g.sample(1).unwrap();
+            assert!(x == "é" || (' '..='~').contains(&x.chars().next().unwrap()), "{:?}", x);
+        }
+        assert_eq!(g.sample(0), None);
+        assert_eq!(GeneratorBuilder::new().build(&dfa(r#"ab"#)).sample(3), None);
+    }
+}
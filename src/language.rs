@@ -0,0 +1,216 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use node::*;
+use transtable::TransTable;
+
+// number of characters an edge matches
+fn edge_size(e: &Edge) -> u128 {
+    e.matches().as_ref().map_or(0, |x| ranges_len(&x.ranges()) as u128)
+}
+
+// properties of the strings accepted by a dfa from start to end of input,
+// the table must be a dfa, see `TransTable::as_dfa`.
+impl TransTable {
+    pub fn is_empty(&self) -> bool {
+        !self.live_states().contains(self.start_id())
+    }
+
+    pub fn is_finite(&self) -> bool {
+        self.is_empty() || self.max_length().is_some()
+    }
+
+    /// number of accepted strings of `n` characters, `None` if it overflows
+    pub fn count(&self, n: usize) -> Option<u128> {
+        let live = self.live_states();
+        let mut counts: HashMap<&States, u128> = HashMap::new();
+        if live.contains(self.start_id()) {
+            counts.insert(self.start_id(), 1);
+        }
+
+        for _ in 0..n {
+            let mut next: HashMap<&States, u128> = HashMap::new();
+            for (state, count) in counts {
+                for e in self.trans_map()[state].iter().filter(|x| live.contains(x.next_node())) {
+                    let c = next.entry(e.next_node()).or_insert(0);
+                    *c = c.checked_add(count.checked_mul(edge_size(e))?)?;
+                }
+            }
+            counts = next;
+        }
+
+        counts
+            .iter()
+            .filter(|&(x, _)| self.end_set().contains(*x))
+            .try_fold(0u128, |r, (_, &c)| r.checked_add(c))
+    }
+
+    /// length of the shortest accepted string, `None` if the language is empty
+    pub fn min_length(&self) -> Option<usize> {
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::new();
+        visited.insert(self.start_id());
+        queue.push_back((self.start_id(), 0));
+
+        while let Some((state, len)) = queue.pop_front() {
+            if self.end_set().contains(state) {
+                return Some(len);
+            }
+
+            for e in &self.trans_map()[state] {
+                if visited.insert(e.next_node()) {
+                    queue.push_back((e.next_node(), len + 1));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// length of the longest accepted string, `None` if the language is empty or infinite
+    pub fn max_length(&self) -> Option<usize> {
+        let live = self.live_states();
+        if !live.contains(self.start_id()) {
+            return None;
+        }
+
+        self.longest(&live)
+    }
+
+    // states which are reachable from start and lead to an end
+    fn live_states(&self) -> HashSet<&States> {
+        let mut reachable = HashSet::new();
+        let mut visit = vec![self.start_id()];
+        while let Some(state) = visit.pop() {
+            if reachable.insert(state) {
+                visit.extend(self.trans_map()[state].iter().map(|x| x.next_node()));
+            }
+        }
+
+        let mut reverse: HashMap<&States, Vec<&States>> = HashMap::new();
+        for (state, edges) in self.trans_map() {
+            for e in edges {
+                reverse.entry(e.next_node()).or_default().push(state);
+            }
+        }
+
+        let mut live = HashSet::new();
+        let mut visit: Vec<&States> = self.end_set().iter().filter(|x| reachable.contains(x)).collect();
+        while let Some(state) = visit.pop() {
+            if live.insert(state) {
+                visit.extend(reverse.get(state).into_iter().flat_map(|x| x.iter()).filter(|x| reachable.contains(*x)));
+            }
+        }
+
+        live
+    }
+
+    // longest path from start to an end, `None` if a cycle is reachable.
+    // states are visited in topological order, so long chains don't use the stack.
+    fn longest(&self, live: &HashSet<&States>) -> Option<usize> {
+        let live_edges = |state: &States| {
+            self.trans_map()[state]
+                .iter()
+                .map(|x| x.next_node())
+                .filter(|x| live.contains(x))
+        };
+
+        let mut in_degree: HashMap<&States, usize> = live.iter().map(|&x| (x, 0)).collect();
+        for &state in live {
+            for n in live_edges(state) {
+                *in_degree.get_mut(n).unwrap() += 1;
+            }
+        }
+
+        let mut order = Vec::with_capacity(live.len());
+        let mut visit: Vec<&States> = in_degree.iter().filter(|x| *x.1 == 0).map(|x| *x.0).collect();
+        while let Some(state) = visit.pop() {
+            order.push(state);
+            for n in live_edges(state) {
+                let d = in_degree.get_mut(n).unwrap();
+                *d -= 1;
+                if *d == 0 {
+                    visit.push(n);
+                }
+            }
+        }
+
+        // states left out of the order are on a cycle
+        if order.len() < live.len() {
+            return None;
+        }
+
+        let mut longest: HashMap<&States, usize> = HashMap::new();
+        for &state in order.iter().rev() {
+            let r = live_edges(state).map(|x| longest[x] + 1).max().unwrap_or(0);
+            longest.insert(state, r);
+        }
+
+        longest.get(self.start_id()).cloned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use transtable::*;
+
+    #[test]
+    fn test_is_empty() {
+        assert!(!dfa(r#"a"#).is_empty());
+        assert!(!dfa(r#"a*"#).is_empty());
+        assert!(dfa(r#"a\bb"#).is_empty());
+        assert!(dfa(r#"a^b"#).is_empty());
+        assert!(!dfa(r#"(?m)a$\n^b"#).is_empty());
+        assert!(dfa(r#"[a-c]+"#).difference(&dfa(r#"\w*"#)).is_empty());
+        assert!(!dfa(r#"a"#).complement().is_empty());
+    }
+
+    #[test]
+    fn test_length() {
+        let t = dfa(r#"ab|c(d|ef)"#);
+        assert!(t.is_finite());
+        assert_eq!(t.min_length(), Some(2));
+        assert_eq!(t.max_length(), Some(3));
+
+        let t = dfa(r#"a{2,5}"#);
+        assert_eq!(t.min_length(), Some(2));
+        assert_eq!(t.max_length(), Some(5));
+
+        let t = dfa(r#"x(ab)+"#);
+        assert!(!t.is_finite());
+        assert_eq!(t.min_length(), Some(3));
+        assert_eq!(t.max_length(), None);
+
+        // loops which can't reach an end don't make it infinite
+        let t = dfa(r#"a|b*c\bd"#);
+        assert!(t.is_finite());
+        assert_eq!(t.max_length(), Some(1));
+
+        let t = dfa(r#"a\bb"#);
+        assert!(t.is_finite());
+        assert_eq!(t.min_length(), None);
+        assert_eq!(t.max_length(), None);
+
+        // long chains don't overflow the stack
+        let t = dfa(r#"a{20000}"#);
+        assert_eq!(t.max_length(), Some(20000));
+    }
+
+    #[test]
+    fn test_count() {
+        let t = dfa(r#"[ab]c?[0-9]"#);
+        assert_eq!(t.count(1), Some(0));
+        assert_eq!(t.count(2), Some(20));
+        assert_eq!(t.count(3), Some(20));
+        assert_eq!(t.count(4), Some(0));
+
+        let t = dfa(r#"(a|b)*"#);
+        assert_eq!(t.count(0), Some(1));
+        assert_eq!(t.count(10), Some(1024));
+
+        // overlapped alternatives are counted once
+        assert_eq!(dfa(r#"a|[a-c]"#).count(1), Some(3));
+        assert_eq!(dfa(r#"[\u{d000}-\u{efff}]"#).count(1), Some(0x2000 - 0x800));
+        assert_eq!(dfa(r#"."#).count(1), Some(0x10ffff + 1 - 0x800 - 1));
+        assert_eq!(dfa(r#"[\s\S]*"#).count(100), None);
+    }
+}
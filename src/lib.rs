@@ -1,3 +1,5 @@
+// tests compare with bool literals on purpose
+#![cfg_attr(test, allow(clippy::bool_assert_comparison))]
 
 extern crate itertools;
 
@@ -21,4 +23,11 @@ mod node;
 pub mod transtable;
 pub mod dot_graph;
 pub mod execute_engine;
+pub mod product;
+pub mod language;
+pub mod generator;
+pub mod corpus;
+pub mod elimination;
+pub mod codegen;
+pub mod build_helper;
 
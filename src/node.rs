@@ -1,5 +1,7 @@
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
 use std::collections::BTreeSet;
+use std::fmt;
 
 static ID_SEQ: AtomicUsize = AtomicUsize::new(0);
 
@@ -83,19 +85,156 @@ impl NFAGraph {
     }
 }
 
+// class of the character at one side of a position, `Text` is the start or end of input
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Context {
+    Text,
+    Newline,
+    Word,
+    Other,
+}
+
+impl Context {
+    pub fn of(c: Option<char>) -> Context {
+        match c {
+            None => Context::Text,
+            Some('\n') => Context::Newline,
+            Some(c) if c.is_ascii_alphanumeric() || c == '_' => Context::Word,
+            Some(_) => Context::Other,
+        }
+    }
+
+    pub fn mask(self) -> u8 {
+        1 << self as u8
+    }
+
+    // characters of the class, sorted and disjoint
+    pub fn ranges(self) -> Vec<(char, char)> {
+        let word = vec![('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')];
+
+        match self {
+            Context::Text => vec![],
+            Context::Newline => vec![('\n', '\n')],
+            Context::Word => word,
+            Context::Other => complement_ranges(&normalize_ranges(
+                word.into_iter().chain(Some(('\n', '\n'))).collect(),
+            )),
+        }
+    }
+}
+
+pub const ALL_CONTEXTS: u8 = 0b1111;
+pub const CONTEXTS: [Context; 4] = [Context::Text, Context::Newline, Context::Word, Context::Other];
+
+// zero-width assertions, tested against the contexts before and after a position
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Assertion {
+    StartText,       // '\A', or '^' without multi-line mode
+    EndText,         // '\z', or '$' without multi-line mode
+    StartLine,       // '^' in multi-line mode
+    EndLine,         // '$' in multi-line mode
+    WordBoundary,    // '\b'
+    NotWordBoundary, // '\B'
+}
+
+impl Assertion {
+    pub fn holds(self, prev: Context, next: Context) -> bool {
+        match self {
+            Assertion::StartText => prev == Context::Text,
+            Assertion::EndText => next == Context::Text,
+            Assertion::StartLine => prev == Context::Text || prev == Context::Newline,
+            Assertion::EndLine => next == Context::Text || next == Context::Newline,
+            Assertion::WordBoundary => (prev == Context::Word) != (next == Context::Word),
+            Assertion::NotWordBoundary => (prev == Context::Word) == (next == Context::Word),
+        }
+    }
+
+    // the next contexts which make the assertion hold
+    pub fn next_mask(self, prev: Context) -> u8 {
+        [Context::Text, Context::Newline, Context::Word, Context::Other]
+            .iter()
+            .filter(|&&next| self.holds(prev, next))
+            .fold(0, |mask, next| mask | next.mask())
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum EdgeMatches {
-    Character(u8),
-    CharacterRange(u8, u8),
+    Character(char),
+    CharacterRange(char, char),
     Not(Vec<EdgeMatches>),
 }
 
 impl EdgeMatches {
-    fn match_character(&self, c: u8) -> bool {
-        match self {
-            &EdgeMatches::Character(ch) => c == ch,
-            &EdgeMatches::CharacterRange(s, e) => c >= s && c <= e,
-            &EdgeMatches::Not(ref list) => !list.iter().any(|x| x.match_character(c)),
+    fn match_character(&self, c: char) -> bool {
+        match *self {
+            EdgeMatches::Character(ch) => c == ch,
+            EdgeMatches::CharacterRange(s, e) => c >= s && c <= e,
+            EdgeMatches::Not(ref list) => !list.iter().any(|x| x.match_character(c)),
+        }
+    }
+
+    // extend the matches with the other cases of every matched character
+    pub fn fold_case(&self) -> Vec<EdgeMatches> {
+        match *self {
+            EdgeMatches::Character(c) => case_variants(c).into_iter().map(EdgeMatches::Character).collect(),
+            EdgeMatches::CharacterRange(s, e) => {
+                // only the cased characters inside the range can add others
+                let cased = cased_characters();
+                let from = cased.partition_point(|&x| x < s);
+                let to = cased.partition_point(|&x| x <= e);
+                let mut others: Vec<char> = cased[from..to]
+                    .iter()
+                    .flat_map(|&x| case_variants(x))
+                    .filter(|&x| x < s || x > e)
+                    .collect();
+                others.sort();
+                others.dedup();
+
+                let mut r = vec![EdgeMatches::CharacterRange(s, e)];
+                let mut iter = others.into_iter().peekable();
+                while let Some(start) = iter.next() {
+                    let mut end = start;
+                    while let Some(next) = iter.next_if(|&x| x as u32 == end as u32 + 1) {
+                        end = next;
+                    }
+
+                    if start == end {
+                        r.push(EdgeMatches::Character(start));
+                    } else {
+                        r.push(EdgeMatches::CharacterRange(start, end));
+                    }
+                }
+
+                r
+            }
+            EdgeMatches::Not(ref list) => {
+                vec![EdgeMatches::Not(list.iter().flat_map(|x| x.fold_case()).collect())]
+            }
+        }
+    }
+
+    // matched characters as sorted, disjoint and non-adjacent ranges
+    pub fn ranges(&self) -> Vec<(char, char)> {
+        match *self {
+            EdgeMatches::Character(c) => vec![(c, c)],
+            EdgeMatches::CharacterRange(s, e) => vec![(s, e)],
+            EdgeMatches::Not(ref list) => {
+                complement_ranges(&normalize_ranges(list.iter().flat_map(|x| x.ranges()).collect()))
+            }
+        }
+    }
+
+    // inverse of `ranges`, nothing is matched by an empty list
+    pub fn from_ranges(ranges: &[(char, char)]) -> Option<EdgeMatches> {
+        match *ranges {
+            [] => None,
+            [(s, e)] if s == e => Some(EdgeMatches::Character(s)),
+            [(s, e)] => Some(EdgeMatches::CharacterRange(s, e)),
+            _ => {
+                let list = ranges.iter().map(|&(s, e)| EdgeMatches::CharacterRange(s, e)).collect();
+                Some(EdgeMatches::Not(vec![EdgeMatches::Not(list)]))
+            }
         }
     }
 
@@ -113,7 +252,143 @@ impl EdgeMatches {
     }
 }
 
-fn range_intersect(ls: &u8, le: &u8, rs: &u8, re: &u8) -> bool {
+// the character itself and its single character lower and upper cases
+fn case_variants(c: char) -> Vec<char> {
+    let mut r = vec![c];
+    let lower: Vec<char> = c.to_lowercase().collect();
+    let upper: Vec<char> = c.to_uppercase().collect();
+
+    for cases in &[lower, upper] {
+        if cases.len() == 1 && !r.contains(&cases[0]) {
+            r.push(cases[0]);
+        }
+    }
+
+    r
+}
+
+// sorted characters which have another case, scanned once
+fn cased_characters() -> &'static [char] {
+    static CASED: OnceLock<Vec<char>> = OnceLock::new();
+
+    CASED.get_or_init(|| {
+        (0..=char::MAX as u32)
+            .filter_map(char::from_u32)
+            .filter(|&c| case_variants(c).len() > 1)
+            .collect()
+    })
+}
+
+// neighbours of a character, surrogates are skipped
+pub fn char_after(c: char) -> Option<char> {
+    match c {
+        '\u{d7ff}' => Some('\u{e000}'),
+        _ => char::from_u32(c as u32 + 1),
+    }
+}
+
+pub fn char_before(c: char) -> Option<char> {
+    match c {
+        '\0' => None,
+        '\u{e000}' => Some('\u{d7ff}'),
+        _ => char::from_u32(c as u32 - 1),
+    }
+}
+
+pub fn normalize_ranges(mut ranges: Vec<(char, char)>) -> Vec<(char, char)> {
+    ranges.sort();
+
+    let mut r: Vec<(char, char)> = vec![];
+    for (s, e) in ranges {
+        match r.last_mut() {
+            Some(last) if char_after(last.1).is_none_or(|x| x >= s) => last.1 = last.1.max(e),
+            _ => r.push((s, e)),
+        }
+    }
+
+    r
+}
+
+// `ranges` must be normalized
+pub fn complement_ranges(ranges: &[(char, char)]) -> Vec<(char, char)> {
+    let mut r = vec![];
+    let mut next = Some('\0');
+
+    for &(s, e) in ranges {
+        if let Some(start) = next {
+            if start < s {
+                r.push((start, char_before(s).unwrap()));
+            }
+        }
+        next = char_after(e);
+    }
+
+    if let Some(start) = next {
+        r.push((start, char::MAX));
+    }
+
+    r
+}
+
+// number of characters in the ranges, surrogates are not characters
+pub fn ranges_len(ranges: &[(char, char)]) -> u32 {
+    ranges
+        .iter()
+        .map(|&(s, e)| {
+            let surrogates = if s < '\u{e000}' && e > '\u{d7ff}' { 0x800 } else { 0 };
+            e as u32 - s as u32 + 1 - surrogates
+        })
+        .sum()
+}
+
+// the `n`th character of the ranges, counted as `ranges_len` does
+pub fn nth_char(ranges: &[(char, char)], mut n: u32) -> Option<char> {
+    for &(s, e) in ranges {
+        let len = ranges_len(&[(s, e)]);
+        if n >= len {
+            n -= len;
+            continue;
+        }
+
+        let c = s as u32 + n;
+        return ::std::char::from_u32(if (s as u32) < 0xd800 && c >= 0xd800 { c + 0x800 } else { c });
+    }
+
+    None
+}
+
+// a character of the ranges to show in examples, printable ascii is preferred
+pub fn representative(ranges: &[(char, char)]) -> Option<char> {
+    ranges
+        .iter()
+        .find(|&&(s, e)| s <= '~' && e >= ' ')
+        .map(|&(s, _)| s.max(' '))
+        .or_else(|| ranges.first().map(|&(s, _)| s))
+}
+
+// both `lhs` and `rhs` must be normalized
+pub fn intersect_ranges(lhs: &[(char, char)], rhs: &[(char, char)]) -> Vec<(char, char)> {
+    let mut r = vec![];
+    let (mut i, mut j) = (0, 0);
+
+    while i < lhs.len() && j < rhs.len() {
+        let s = lhs[i].0.max(rhs[j].0);
+        let e = lhs[i].1.min(rhs[j].1);
+        if s <= e {
+            r.push((s, e));
+        }
+
+        if lhs[i].1 < rhs[j].1 {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    r
+}
+
+fn range_intersect(ls: &char, le: &char, rs: &char, re: &char) -> bool {
     (rs <= ls && ls <= re) ||
     (rs <= le && le <= re) ||
     (ls <= rs && rs <= le) ||
@@ -121,32 +396,31 @@ fn range_intersect(ls: &u8, le: &u8, rs: &u8, re: &u8) -> bool {
 }
 
 #[inline]
-fn display(c: &u8) -> String {
+fn display(c: &char) -> String {
     match *c {
-        b'\n' => "<br>".to_string(),
-        _ => format!("'{}'", *c as char),
+        '\n' => "<br>".to_string(),
+        _ => format!("'{}'", *c),
     }
 }
 
-impl ToString for EdgeMatches {
-    fn to_string(&self) -> String {
+impl fmt::Display for EdgeMatches {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            EdgeMatches::Character(c) => format!("{}", display(c)),
-            EdgeMatches::CharacterRange(s, e) => format!("{}-{}", display(s), display(e)),
+            EdgeMatches::Character(c) => write!(f, "{}", display(c)),
+            EdgeMatches::CharacterRange(s, e) => write!(f, "{}-{}", display(s), display(e)),
             EdgeMatches::Not(list) => {
-                let mut s = "Not ".to_string();
+                write!(f, "Not ")?;
                 let mut iter = list.iter();
 
                 if let Some(item) = iter.next() {
-                    s.push_str(&item.to_string());
+                    write!(f, "{}", item)?;
                 }
 
-                for item in iter.next() {
-                    s.push_str(", ");
-                    s.push_str(&item.to_string());
+                for item in iter {
+                    write!(f, ", {}", item)?;
                 }
 
-                s
+                Ok(())
             }
         }
     }
@@ -155,6 +429,8 @@ impl ToString for EdgeMatches {
 #[derive(Clone, Debug)]
 pub struct Edge {
     matches: Option<EdgeMatches>,
+    assertion: Option<Assertion>,
+    slot: Option<usize>,
     next_node: States,
 }
 
@@ -162,32 +438,72 @@ impl Edge {
     pub fn epsilon(next_node: States) -> Edge {
         Edge {
             matches: None,
+            assertion: None,
+            slot: None,
             next_node,
         }
     }
 
     pub fn new(dest: States, matches: Option<EdgeMatches>) -> Edge {
         Edge {
-            matches: matches,
+            matches,
+            assertion: None,
+            slot: None,
             next_node: dest,
         }
     }
 
+    // an epsilon edge which saves current position into the capture `slot`
+    pub fn save(dest: States, slot: usize) -> Edge {
+        Edge {
+            matches: None,
+            assertion: None,
+            slot: Some(slot),
+            next_node: dest,
+        }
+    }
+
+    // a zero-width edge which can only be passed if the assertion holds
+    pub fn look_around(dest: States, assertion: Assertion) -> Edge {
+        Edge {
+            matches: None,
+            assertion: Some(assertion),
+            slot: None,
+            next_node: dest,
+        }
+    }
+
+    // the same edge to another state
+    pub fn redirect(&self, dest: States) -> Edge {
+        Edge {
+            next_node: dest,
+            ..self.clone()
+        }
+    }
+
     pub fn matches(&self) -> &Option<EdgeMatches> {
         &self.matches
     }
 
+    pub fn assertion(&self) -> Option<Assertion> {
+        self.assertion
+    }
+
+    pub fn slot(&self) -> Option<usize> {
+        self.slot
+    }
+
     pub fn next_node(&self) -> &States {
         &self.next_node
     }
 
-    pub fn match_character(&self, c: u8) -> bool {
-        self.matches.as_ref().map_or(false, |ref x| x.match_character(c))
+    pub fn match_character(&self, c: char) -> bool {
+        self.matches.as_ref().is_some_and(|x| x.match_character(c))
     }
 
     pub fn intersect(&self, e: &Edge) -> bool {
         match (self.matches.as_ref(), e.matches.as_ref()) {
-            (Some(lhs), Some(rhs)) => lhs.intersect(&rhs),
+            (Some(lhs), Some(rhs)) => lhs.intersect(rhs),
             _ => false,
         }
     }
@@ -240,62 +556,89 @@ mod test {
     #[test]
     fn test_edge_intersect() {
         let l = Edge::new(set![0], None);
-        let r = Edge::new(set![0], Some(EdgeMatches::Character(b'c')));
+        let r = Edge::new(set![0], Some(EdgeMatches::Character('c')));
         assert_eq!(l.intersect(&r), false);
         assert_eq!(r.intersect(&l), false);
 
-        let l = Edge::new(set![0], Some(EdgeMatches::Character(b'c')));
+        let l = Edge::new(set![0], Some(EdgeMatches::Character('c')));
         assert_eq!(l.intersect(&r), true);
         assert_eq!(r.intersect(&l), true);
 
-        let l = Edge::new(set![0], Some(EdgeMatches::CharacterRange(b'a', b'z')));
+        let l = Edge::new(set![0], Some(EdgeMatches::CharacterRange('a', 'z')));
         assert_eq!(l.intersect(&r), true);
         assert_eq!(r.intersect(&l), true);
 
-        let r = Edge::new(set![0], Some(EdgeMatches::Not(vec![EdgeMatches::Character(b'c')])));
+        let r = Edge::new(set![0], Some(EdgeMatches::Not(vec![EdgeMatches::Character('c')])));
         assert_eq!(l.intersect(&r), false);
         assert_eq!(r.intersect(&l), false);
 
-        let r = Edge::new(set![0], Some(EdgeMatches::CharacterRange(b'0', b'9')));
+        let r = Edge::new(set![0], Some(EdgeMatches::CharacterRange('0', '9')));
         assert_eq!(l.intersect(&r), false);
         assert_eq!(r.intersect(&l), false);
 
-        let r = Edge::new(set![0], Some(EdgeMatches::CharacterRange(b'd', b'f')));
+        let r = Edge::new(set![0], Some(EdgeMatches::CharacterRange('d', 'f')));
         assert_eq!(l.intersect(&r), true);
         assert_eq!(r.intersect(&l), true);
 
-        let r = Edge::new(set![0], Some(EdgeMatches::CharacterRange(b'A', b'f')));
+        let r = Edge::new(set![0], Some(EdgeMatches::CharacterRange('A', 'f')));
         assert_eq!(l.intersect(&r), true);
         assert_eq!(r.intersect(&l), true);
     }
 
+    #[test]
+    fn test_edge_ranges() {
+        let m = EdgeMatches::Not(vec![EdgeMatches::CharacterRange('b', 'd'), EdgeMatches::Character('a')]);
+        assert_eq!(m.ranges(), vec![('\0', '`'), ('e', char::MAX)]);
+
+        let m = EdgeMatches::Not(vec![EdgeMatches::CharacterRange('\u{e000}', char::MAX)]);
+        assert_eq!(m.ranges(), vec![('\0', '\u{d7ff}')]);
+
+        let ranges = normalize_ranges(vec![('x', 'z'), ('a', 'c'), ('b', 'e'), ('f', 'f')]);
+        assert_eq!(ranges, vec![('a', 'f'), ('x', 'z')]);
+        assert_eq!(intersect_ranges(&ranges, &[('c', 'y')]), vec![('c', 'f'), ('x', 'y')]);
+
+        let m = EdgeMatches::from_ranges(&intersect_ranges(&ranges, &Context::Other.ranges()));
+        assert!(m.is_none());
+        assert_eq!(Context::Other.ranges()[..2], [('\0', '\t'), ('\u{b}', '/')]);
+    }
+
     #[test]
     fn test_edge_match_character() {
         let edge = Edge::new(set![0], None);
-        assert_eq!(edge.match_character(b'c'), false);
-
-        let edge = Edge::new(set![0], Some(EdgeMatches::Character(b'c')));
-        assert_eq!(edge.match_character(b'c'), true);
-        assert_eq!(edge.match_character(b'd'), false);
-
-        let edge = Edge::new(set![0], Some(EdgeMatches::CharacterRange(b'3', b'5')));
-        assert_eq!(edge.match_character(b'2'), false);
-        assert_eq!(edge.match_character(b'3'), true);
-        assert_eq!(edge.match_character(b'4'), true);
-        assert_eq!(edge.match_character(b'5'), true);
-        assert_eq!(edge.match_character(b'6'), false);
-
-        let edge = Edge::new(set![0], Some(EdgeMatches::Not(vec![EdgeMatches::Character(b'3')])));
-        assert_eq!(edge.match_character(b'2'), true);
-        assert_eq!(edge.match_character(b'3'), false);
-        assert_eq!(edge.match_character(b'4'), true);
-
-        let edge = Edge::new(set![0], Some(EdgeMatches::Not(vec![EdgeMatches::CharacterRange(b'3', b'5')])));
-        assert_eq!(edge.match_character(b'2'), true);
-        assert_eq!(edge.match_character(b'3'), false);
-        assert_eq!(edge.match_character(b'4'), false);
-        assert_eq!(edge.match_character(b'5'), false);
-        assert_eq!(edge.match_character(b'6'), true);
+        assert_eq!(edge.match_character('c'), false);
+
+        let edge = Edge::new(set![0], Some(EdgeMatches::Character('c')));
+        assert_eq!(edge.match_character('c'), true);
+        assert_eq!(edge.match_character('d'), false);
+
+        let edge = Edge::new(set![0], Some(EdgeMatches::CharacterRange('3', '5')));
+        assert_eq!(edge.match_character('2'), false);
+        assert_eq!(edge.match_character('3'), true);
+        assert_eq!(edge.match_character('4'), true);
+        assert_eq!(edge.match_character('5'), true);
+        assert_eq!(edge.match_character('6'), false);
+
+        let edge = Edge::new(set![0], Some(EdgeMatches::Not(vec![EdgeMatches::Character('3')])));
+        assert_eq!(edge.match_character('2'), true);
+        assert_eq!(edge.match_character('3'), false);
+        assert_eq!(edge.match_character('4'), true);
+
+        let edge = Edge::new(set![0], Some(EdgeMatches::Not(vec![EdgeMatches::CharacterRange('3', '5')])));
+        assert_eq!(edge.match_character('2'), true);
+        assert_eq!(edge.match_character('3'), false);
+        assert_eq!(edge.match_character('4'), false);
+        assert_eq!(edge.match_character('5'), false);
+        assert_eq!(edge.match_character('6'), true);
     }
-}
 
+    #[test]
+    fn test_edge_fold_case() {
+        let ranges = |m: EdgeMatches| m.fold_case().iter().flat_map(|x| x.ranges()).collect::<Vec<_>>();
+
+        assert_eq!(ranges(EdgeMatches::CharacterRange('a', 'c')), [('a', 'c'), ('A', 'C')]);
+        assert_eq!(ranges(EdgeMatches::CharacterRange('X', 'b')), [('X', 'b'), ('A', 'B'), ('x', 'z')]);
+        assert_eq!(ranges(EdgeMatches::Character('k')), [('k', 'k'), ('K', 'K')]);
+        // the whole range folds to itself
+        assert_eq!(ranges(EdgeMatches::CharacterRange('\0', char::MAX)), [('\0', char::MAX)]);
+    }
+}
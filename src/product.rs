@@ -0,0 +1,318 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use node::*;
+use regex_gen::RegexItem;
+use transtable::TransTable;
+
+// a state of the product of two dfa, `None` is the dead state
+type Pair = (Option<States>, Option<States>);
+
+fn next_state(table: &TransTable, state: &Option<States>, c: char) -> Option<States> {
+    table.next_state(state.as_ref()?, c).cloned()
+}
+
+// edges of a pair state, split into disjoint classes and grouped by the pair they lead to.
+// a `complete` walk keeps the edges into the dead pair, so every character has an edge.
+fn pair_edges(lhs: &TransTable, rhs: &TransTable, pair: &Pair, complete: bool) -> Vec<(Pair, Vec<(char, char)>)> {
+    let edges = |table: &TransTable, state: &Option<States>| -> Vec<(char, char)> {
+        state.as_ref().map_or(vec![], |x| {
+            table.trans_map()[x]
+                .iter()
+                .flat_map(|e| e.matches().as_ref().map_or(vec![], |m| m.ranges()))
+                .collect()
+        })
+    };
+
+    let mut bounds: Vec<char> = edges(lhs, &pair.0)
+        .into_iter()
+        .chain(edges(rhs, &pair.1))
+        .flat_map(|(s, e)| Some(s).into_iter().chain(char_after(e)))
+        .chain(if complete { Some('\0') } else { None })
+        .collect();
+    bounds.sort();
+    bounds.dedup();
+
+    let mut r: Vec<(Pair, Vec<(char, char)>)> = vec![];
+    for (i, &s) in bounds.iter().enumerate() {
+        let e = bounds.get(i + 1).map_or(char::MAX, |&x| char_before(x).unwrap());
+        let next = (next_state(lhs, &pair.0, s), next_state(rhs, &pair.1, s));
+        if next == (None, None) && !complete {
+            continue;
+        }
+
+        match r.iter_mut().find(|(x, _)| *x == next) {
+            Some((_, ranges)) => ranges.push((s, e)),
+            None => r.push((next, vec![(s, e)])),
+        }
+    }
+
+    r
+}
+
+// pair of the start states, in context `ctx` if it is given
+fn start_pair(lhs: &TransTable, rhs: &TransTable, ctx: Option<Context>) -> Pair {
+    match ctx {
+        Some(ctx) => (Some(lhs.start_in(ctx).clone()), Some(rhs.start_in(ctx).clone())),
+        None => (Some(lhs.start_id().clone()), Some(rhs.start_id().clone())),
+    }
+}
+
+fn is_end(table: &TransTable, state: &Option<States>, ctx: Context) -> bool {
+    state.as_ref().is_some_and(|x| table.is_end_in(x, ctx))
+}
+
+/// difference between the languages of two tables, see `TransTable::compare`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    only_lhs: Option<String>,
+    only_rhs: Option<String>,
+}
+
+impl Comparison {
+    pub fn is_equal(&self) -> bool {
+        self.only_lhs.is_none() && self.only_rhs.is_none()
+    }
+
+    /// every string accepted by the right side is accepted by the left side
+    pub fn includes(&self) -> bool {
+        self.only_rhs.is_none()
+    }
+
+    /// every string accepted by the left side is accepted by the right side
+    pub fn is_included(&self) -> bool {
+        self.only_lhs.is_none()
+    }
+
+    /// shortest string accepted by the left side only
+    pub fn only_lhs(&self) -> Option<&str> {
+        self.only_lhs.as_deref()
+    }
+
+    /// shortest string accepted by the right side only
+    pub fn only_rhs(&self) -> Option<&str> {
+        self.only_rhs.as_deref()
+    }
+
+    /// shortest string accepted by one side but not the other
+    pub fn counterexample(&self) -> Option<&str> {
+        match (self.only_lhs(), self.only_rhs()) {
+            (Some(l), Some(r)) if r.chars().count() < l.chars().count() => Some(r),
+            (Some(l), _) => Some(l),
+            (None, r) => r,
+        }
+    }
+}
+
+impl TransTable {
+    /// compare languages of two dfa by walking their product breadth first,
+    /// so the strings found are the shortest ones.
+    pub fn compare(&self, other: &TransTable) -> Comparison {
+        let mut r = Comparison {
+            only_lhs: None,
+            only_rhs: None,
+        };
+
+        let start = start_pair(self, other, None);
+        let mut seen: HashSet<Pair> = HashSet::new();
+        let mut queue = VecDeque::new();
+        seen.insert(start.clone());
+        queue.push_back((start, String::new()));
+
+        while let Some((pair, s)) = queue.pop_front() {
+            match (is_end(self, &pair.0, Context::Text), is_end(other, &pair.1, Context::Text)) {
+                (true, false) if r.only_lhs.is_none() => r.only_lhs = Some(s.clone()),
+                (false, true) if r.only_rhs.is_none() => r.only_rhs = Some(s.clone()),
+                _ => {}
+            }
+
+            if r.only_lhs.is_some() && r.only_rhs.is_some() {
+                break;
+            }
+
+            for (next, ranges) in pair_edges(self, other, &pair, false) {
+                if seen.insert(next.clone()) {
+                    let mut s = s.clone();
+                    s.extend(representative(&ranges));
+                    queue.push_back((next, s));
+                }
+            }
+        }
+
+        r
+    }
+
+    /// strings accepted by both dfa
+    pub fn intersection(&self, other: &TransTable) -> TransTable {
+        self.product(other, |a, b| a && b)
+    }
+
+    /// strings accepted by either dfa
+    pub fn union(&self, other: &TransTable) -> TransTable {
+        self.product(other, |a, b| a || b)
+    }
+
+    /// strings accepted by this dfa but not the other one
+    pub fn difference(&self, other: &TransTable) -> TransTable {
+        self.product(other, |a, b| a && !b)
+    }
+
+    /// strings not accepted by this dfa, over the whole alphabet
+    pub fn complement(&self) -> TransTable {
+        self.product(self, |a, _| !a)
+    }
+
+    // dfa of the product, a pair state accepts if `op` of the two sides does.
+    // the dead pair is kept as an explicit state if `op` accepts it.
+    fn product<F: Fn(bool, bool) -> bool>(&self, other: &TransTable, op: F) -> TransTable {
+        let complete = op(false, false);
+        let contexts: Vec<Context> = if self.is_context_dependent() || other.is_context_dependent() {
+            CONTEXTS.to_vec()
+        } else {
+            vec![]
+        };
+
+        let mut pairs: Vec<Pair> = vec![];
+        let mut index: HashMap<Pair, usize> = HashMap::new();
+        let mut id_of = |pair: Pair, pairs: &mut Vec<Pair>| -> States {
+            let id = *index.entry(pair.clone()).or_insert_with(|| {
+                pairs.push(pair);
+                pairs.len() - 1
+            });
+
+            set![id]
+        };
+
+        let start = id_of(start_pair(self, other, None), &mut pairs);
+        let context_start: HashMap<Context, States> = contexts
+            .iter()
+            .map(|&ctx| (ctx, id_of(start_pair(self, other, Some(ctx)), &mut pairs)))
+            .collect();
+
+        let mut trans: HashMap<States, Vec<Edge>> = HashMap::new();
+        let mut i = 0;
+        while i < pairs.len() {
+            let edges = pair_edges(self, other, &pairs[i].clone(), complete)
+                .into_iter()
+                .map(|(next, ranges)| {
+                    let matches = EdgeMatches::from_ranges(&normalize_ranges(ranges));
+                    Edge::new(id_of(next, &mut pairs), matches)
+                })
+                .collect();
+            trans.insert(set![i], edges);
+            i += 1;
+        }
+
+        let ends = |ctx: Context| -> HashSet<States> {
+            pairs
+                .iter()
+                .enumerate()
+                .filter(|(_, (a, b))| op(is_end(self, a, ctx), is_end(other, b, ctx)))
+                .map(|(i, _)| set![i])
+                .collect()
+        };
+        let context_end = contexts
+            .iter()
+            .filter(|&&ctx| ctx != Context::Text)
+            .map(|&ctx| (ctx, ends(ctx)))
+            .collect();
+
+        TransTable::from_parts(start, ends(Context::Text), trans, context_start, context_end)
+    }
+}
+
+impl RegexItem {
+    /// compare languages of two patterns, see `TransTable::compare`
+    pub fn compare(&self, other: &RegexItem) -> Comparison {
+        let dfa = |item: &RegexItem| {
+            let mut t = TransTable::from_nfa(&item.nfa_graph());
+            t.as_dfa();
+            t
+        };
+
+        dfa(self).compare(&dfa(other))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use dot_graph::*;
+    use execute_engine::*;
+    use product::*;
+    use regex_gen::*;
+    use transtable::dfa;
+
+    fn compare(lhs: &str, rhs: &str) -> Comparison {
+        let lhs: RegexItem = lhs.into();
+        let rhs: RegexItem = rhs.into();
+
+        lhs.compare(&rhs)
+    }
+
+    #[test]
+    fn test_compare() {
+        assert!(compare(r#"a+"#, r#"aa*"#).is_equal());
+        assert!(compare(r#"(a|b)*"#, r#"(a*b*)*"#).is_equal());
+        assert!(compare(r#"^\d+$"#, r#"[0-9]+"#).is_equal());
+        assert!(compare(r#"(?i)ab"#, r#"[aA][bB]"#).is_equal());
+
+        let c = compare(r#"a*"#, r#"a+"#);
+        assert!(c.includes());
+        assert!(!c.is_included());
+        assert_eq!(c.only_lhs(), Some(""));
+        assert_eq!(c.counterexample(), Some(""));
+
+        let c = compare(r#"a+|b+|c+"#, r#"[a-c]+"#);
+        assert!(c.is_included());
+        assert_eq!(c.only_rhs(), Some("ab"));
+
+        let c = compare(r#"x[^a]y|xay"#, r#"x.y"#);
+        assert!(c.includes());
+        assert_eq!(c.only_lhs(), Some("x\ny"));
+
+        let c = compare(r#"colou?r"#, r#"colou+r"#);
+        assert_eq!(c.only_lhs(), Some("color"));
+        assert_eq!(c.only_rhs(), Some("colouur"));
+        assert_eq!(c.counterexample(), Some("color"));
+
+        let c = compare(r#"\w+@\w+"#, r#"[a-z]+@[a-z]+"#);
+        assert!(c.includes());
+        assert_eq!(c.only_lhs(), Some("0@0"));
+    }
+
+    #[test]
+    fn test_boolean_operations() {
+        let ident = dfa(r#"[a-z_][a-z0-9_]*"#);
+        let keyword = dfa(r#"if|else|fn"#);
+
+        let e = ExecuteEngine::with_transtable(ident.difference(&keyword));
+        assert!(e.exact_match("iff"));
+        assert!(e.exact_match("foo"));
+        assert!(!e.exact_match("if"));
+        assert!(!e.exact_match("fn"));
+        assert!(!e.exact_match("1x"));
+        assert_eq!(e.find("if else x1"), Some((0, 1)));
+
+        let t = dfa(r#"[a-c]+"#).intersection(&dfa(r#"[b-d]+"#));
+        assert!(t.compare(&dfa(r#"[bc]+"#)).is_equal());
+
+        let t = dfa(r#"a+"#).union(&dfa(r#"b+"#));
+        assert!(t.compare(&dfa(r#"a+|b+"#)).is_equal());
+
+        let t = dfa(r#"a*"#).complement();
+        assert!(t.complement().compare(&dfa(r#"a*"#)).is_equal());
+        assert!(!t.to_dot_graph().is_empty());
+
+        let e = ExecuteEngine::with_transtable(t);
+        assert!(!e.exact_match(""));
+        assert!(!e.exact_match("aa"));
+        assert!(e.exact_match("b"));
+        assert!(e.exact_match("ab"));
+        assert!(e.exact_match("\u{10ffff}"));
+
+        // assertions are kept by the product
+        let t = dfa(r#"\ba"#).intersection(&dfa(r#"a\b"#));
+        let e = ExecuteEngine::with_transtable(t);
+        let r: Vec<(usize, usize)> = e.find_iter("xa a ab").collect();
+        assert_eq!(r, vec![(3, 4)]);
+    }
+}
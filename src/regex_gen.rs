@@ -1,19 +1,25 @@
 use std::convert::From;
+use std::error::Error;
+use std::fmt;
 use std::iter::Peekable;
-use std::str::Chars;
-use std::string::ToString;
+use std::str::{CharIndices, FromStr};
 
 use node::*;
 
+pub use node::Assertion;
+
 #[derive(Debug, PartialEq)]
 pub enum RegexUnit {
-    Character(u8),
-    CharacterRange(u8, u8),
-    NotCharacter(u8),
+    Character(char),
+    CharacterRange(char, char),
+    NotCharacter(char),
     NotUnits(Vec<RegexUnit>),
     UnitChoice(Vec<RegexUnit>),
     ItemList(Vec<RegexItem>),
     ItemChoice(Vec<RegexItem>),
+    CaseInsensitive(Vec<RegexItem>),
+    Assertion(Assertion),
+    Capture(usize, Option<String>, Vec<RegexItem>), // index and name of a capture group
 }
 
 #[derive(Debug, PartialEq)]
@@ -22,6 +28,7 @@ pub enum RegexAnnotation {
     OneOrZero,   // '?'
     GreaterZero, // '+'
     AnyOccurs,   // '*'
+    Repeat(usize, Option<usize>), // '{n}', '{n,}', '{n,m}'
 }
 
 #[derive(Debug, PartialEq)]
@@ -32,267 +39,675 @@ pub struct RegexItem {
 
 impl<'s> From<&'s str> for RegexItem {
     fn from(s: &'s str) -> RegexItem {
-        RegexParser {
-            input: s.chars().peekable(),
-        }.parse()
-        .unwrap()
+        match s.parse() {
+            Ok(item) => item,
+            Err(e) => panic!("{}", e),
+        }
     }
 }
 
-impl ToString for RegexUnit {
-    fn to_string(&self) -> String {
-        let mut r = String::new();
+impl FromStr for RegexItem {
+    type Err = RegexParserError;
 
-        match self {
-            RegexUnit::Character(c) => match c {
-                b'\n' => r.push_str("\\n"),
-                _ => r.push(*c as char),
-            }
+    fn from_str(s: &str) -> RegexParserResult {
+        RegexBuilder::new().parse(s)
+    }
+}
+
+fn word_units() -> Vec<RegexUnit> {
+    vec![
+        RegexUnit::CharacterRange('0', '9'),
+        RegexUnit::CharacterRange('A', 'Z'),
+        RegexUnit::CharacterRange('a', 'z'),
+        RegexUnit::Character('_'),
+    ]
+}
+
+fn space_units() -> Vec<RegexUnit> {
+    "\t\n\x0b\x0c\r ".chars().map(RegexUnit::Character).collect()
+}
+
+// `\d`, `\w`, `\s` and their negations
+fn perl_class(c: char) -> Option<RegexUnit> {
+    match c {
+        'd' => Some(RegexUnit::CharacterRange('0', '9')),
+        'D' => Some(RegexUnit::NotUnits(vec![RegexUnit::CharacterRange('0', '9')])),
+        'w' => Some(RegexUnit::UnitChoice(word_units())),
+        'W' => Some(RegexUnit::NotUnits(word_units())),
+        's' => Some(RegexUnit::UnitChoice(space_units())),
+        'S' => Some(RegexUnit::NotUnits(space_units())),
+        _ => None,
+    }
+}
+
+// `\d` is left out, it is a plain range and printed as `0-9` inside character groups
+fn perl_class_name(unit: &RegexUnit) -> Option<char> {
+    ['D', 'w', 'W', 's', 'S']
+        .iter()
+        .find(|&&c| perl_class(c).as_ref() == Some(unit))
+        .cloned()
+}
+
+// characters need to be escaped outside and inside of character groups
+const META_CHARACTERS: &str = "\\.+*?()|[]{}^$";
+const CLASS_META_CHARACTERS: &str = "\\[]-^";
+
+fn write_character(f: &mut fmt::Formatter, c: char, meta: &str) -> fmt::Result {
+    match c {
+        '\n' => write!(f, "\\n"),
+        '\t' => write!(f, "\\t"),
+        '\r' => write!(f, "\\r"),
+        '\0' => write!(f, "\\0"),
+        _ if meta.contains(c) => write!(f, "\\{}", c),
+        _ if c == ' ' || !(c.is_control() || c.is_whitespace()) => write!(f, "{}", c),
+        _ if c <= '\u{ff}' => write!(f, "\\x{:02X}", c as u32),
+        _ => write!(f, "\\u{{{:X}}}", c as u32),
+    }
+}
+
+// a member of `[...]`, which is escaped in character group context
+struct ClassMember<'a>(&'a RegexUnit);
+
+impl<'a> fmt::Display for ClassMember<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self.0 {
+            RegexUnit::Character(c) => write_character(f, c, CLASS_META_CHARACTERS),
             RegexUnit::CharacterRange(s, e) => {
-                r.push(*s as char);
-                r.push('-');
-                r.push(*e as char);
-            }
-            RegexUnit::NotCharacter(c) => {
-                match c {
-                    b'\n' => r.push('.'),
-                    _ => {
-                        r.push_str("[^");
-                        r.push(*c as char);
-                        r.push(']');
-                    }
-                }
+                write_character(f, s, CLASS_META_CHARACTERS)?;
+                write!(f, "-")?;
+                write_character(f, e, CLASS_META_CHARACTERS)
             }
+            ref unit => write!(f, "{}", unit),
+        }
+    }
+}
+
+impl fmt::Display for RegexUnit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(c) = perl_class_name(self) {
+            return write!(f, "\\{}", c);
+        }
+
+        match self {
+            RegexUnit::Character(c) => write_character(f, *c, META_CHARACTERS),
+            RegexUnit::CharacterRange(..) => write!(f, "{}", ClassMember(self)),
+            RegexUnit::Assertion(a) => write!(f, "{}", a),
+            RegexUnit::NotCharacter('\n') => write!(f, "."),
+            RegexUnit::NotCharacter(c) => write!(f, "[^{}]", ClassMember(&RegexUnit::Character(*c))),
             RegexUnit::NotUnits(list) => {
-                r.push_str("[^");
+                write!(f, "[^")?;
                 for i in list {
-                    r.push_str(&i.to_string());
+                    write!(f, "{}", ClassMember(i))?;
                 }
-                r.push(']');
+                write!(f, "]")
             }
             RegexUnit::UnitChoice(list) => {
-                r.push('[');
+                write!(f, "[")?;
                 for i in list {
-                    r.push_str(&i.to_string());
+                    write!(f, "{}", ClassMember(i))?;
                 }
-                r.push(']');
+                write!(f, "]")
             }
-            RegexUnit::ItemChoice(list) => {
+            RegexUnit::ItemChoice(list) | RegexUnit::CaseInsensitive(list) | RegexUnit::Capture(_, _, list) => {
                 let mut it = list.iter();
 
-                r.push('(');
+                match self {
+                    RegexUnit::CaseInsensitive(_) => write!(f, "(?i:")?,
+                    RegexUnit::Capture(_, Some(name), _) => write!(f, "(?P<{}>", name)?,
+                    RegexUnit::Capture(..) => write!(f, "(")?,
+                    _ => write!(f, "(?:")?,
+                }
                 if let Some(item) = it.next() {
-                    r.push_str(&item.to_string());
+                    write!(f, "{}", item)?;
                 }
                 for item in it {
-                    r.push('|');
-                    r.push_str(&item.to_string());
+                    write!(f, "|{}", item)?;
                 }
-                r.push(')');
+                write!(f, ")")
             }
             RegexUnit::ItemList(list) => {
                 for i in list {
-                    r.push_str(&i.to_string());
+                    write!(f, "{}", i)?;
                 }
+
+                Ok(())
             }
         }
+    }
+}
 
-        r
+// the only assertion of a group, if it is a line anchor
+fn line_anchor(items: &[RegexItem]) -> Option<Assertion> {
+    let list = match items {
+        [RegexItem { unit: RegexUnit::ItemList(list), annotation: RegexAnnotation::StandAlone }] => list,
+        _ => return None,
+    };
+
+    match list[..] {
+        [RegexItem {
+            unit: RegexUnit::Assertion(a @ Assertion::StartLine),
+            annotation: RegexAnnotation::StandAlone,
+        }]
+        | [RegexItem {
+            unit: RegexUnit::Assertion(a @ Assertion::EndLine),
+            annotation: RegexAnnotation::StandAlone,
+        }] => Some(a),
+        _ => None,
     }
 }
 
-impl ToString for RegexItem {
-    fn to_string(&self) -> String {
-        let mut r = String::new();
+impl fmt::Display for Assertion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Assertion::StartText => "^",
+            Assertion::EndText => "$",
+            Assertion::StartLine => "(?m:^)",
+            Assertion::EndLine => "(?m:$)",
+            Assertion::WordBoundary => "\\b",
+            Assertion::NotWordBoundary => "\\B",
+        };
 
-        r.push_str(&self.unit.to_string());
+        write!(f, "{}", s)
+    }
+}
 
-        match self.annotation {
-            RegexAnnotation::AnyOccurs => r.push('*'),
-            RegexAnnotation::OneOrZero => r.push('?'),
-            RegexAnnotation::GreaterZero => r.push('+'),
-            _ => {}
+impl fmt::Display for RegexItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.unit {
+            RegexUnit::CharacterRange('0', '9') => write!(f, "\\d")?,
+            RegexUnit::CharacterRange(..) => write!(f, "[{}]", self.unit)?,
+            ref unit => write!(f, "{}", unit)?,
         }
 
-        r
+        match self.annotation {
+            RegexAnnotation::AnyOccurs => write!(f, "*"),
+            RegexAnnotation::OneOrZero => write!(f, "?"),
+            RegexAnnotation::GreaterZero => write!(f, "+"),
+            RegexAnnotation::Repeat(n, Some(m)) if n == m => write!(f, "{{{}}}", n),
+            RegexAnnotation::Repeat(n, Some(m)) => write!(f, "{{{},{}}}", n, m),
+            RegexAnnotation::Repeat(n, None) => write!(f, "{{{},}}", n),
+            RegexAnnotation::StandAlone => Ok(()),
+        }
     }
 }
 
 impl RegexUnit {
-    fn nfa_graph(&self) -> NFAGraph {
+    // matches of a character group member
+    fn edge_matches(&self) -> EdgeMatches {
+        match *self {
+            RegexUnit::Character(c) => EdgeMatches::Character(c),
+            RegexUnit::CharacterRange(s, e) => EdgeMatches::CharacterRange(s, e),
+            RegexUnit::NotCharacter(c) => EdgeMatches::Not(vec![EdgeMatches::Character(c)]),
+            RegexUnit::NotUnits(ref list) => EdgeMatches::Not(list.iter().map(|x| x.edge_matches()).collect()),
+            RegexUnit::UnitChoice(ref list) => {
+                EdgeMatches::Not(vec![EdgeMatches::Not(list.iter().map(|x| x.edge_matches()).collect())])
+            }
+            RegexUnit::ItemList(_)
+            | RegexUnit::ItemChoice(_)
+            | RegexUnit::CaseInsensitive(_)
+            | RegexUnit::Assertion(_)
+            | RegexUnit::Capture(..) => unreachable!(),
+        }
+    }
+
+    // count of nfa nodes generated by `nfa_graph`
+    fn nfa_size(&self) -> usize {
         match self {
-            &RegexUnit::Character(c) => {
+            RegexUnit::UnitChoice(list) => list.iter().fold(2, |n, x| n.saturating_add(x.nfa_size())),
+            RegexUnit::ItemChoice(list) | RegexUnit::CaseInsensitive(list) | RegexUnit::Capture(_, _, list) => {
+                list.iter().fold(2, |n, x| n.saturating_add(x.nfa_size()))
+            }
+            RegexUnit::ItemList(list) if list.is_empty() => 2,
+            RegexUnit::ItemList(list) => list.iter().fold(0, |n, x| n.saturating_add(x.nfa_size())),
+            _ => 2,
+        }
+    }
+
+    fn nfa_graph(&self, case_insensitive: bool) -> NFAGraph {
+        match *self {
+            RegexUnit::Character(_)
+            | RegexUnit::CharacterRange(..)
+            | RegexUnit::NotCharacter(_)
+            | RegexUnit::NotUnits(_) => {
                 let mut graph = NFAGraph::new();
-                {
-                    let end_id = graph.end_id();
-                    let (start, _) = graph.nodes_mut();
+                let end_id = graph.end_id();
 
-                    start.connect(set![end_id], Some(EdgeMatches::Character(c)));
+                let matches = if case_insensitive {
+                    self.edge_matches().fold_case()
+                } else {
+                    vec![self.edge_matches()]
+                };
+                for m in matches {
+                    graph.start_mut().connect(set![end_id], Some(m));
                 }
 
                 graph
             }
-            &RegexUnit::CharacterRange(s, e) => {
+            RegexUnit::Assertion(a) => {
                 let mut graph = NFAGraph::new();
-                {
-                    let end_id = graph.end_id();
-                    let (start, _) = graph.nodes_mut();
-
-                    start.connect(set![end_id], Some(EdgeMatches::CharacterRange(s, e)));
-                }
+                let end_id = graph.end_id();
+                graph.start_mut().append_edge(Edge::look_around(set![end_id], a));
 
                 graph
             }
-            &RegexUnit::NotCharacter(c) => {
-                let mut graph = NFAGraph::new();
-                {
-                    let end_id = graph.end_id();
-                    let (start, _) = graph.nodes_mut();
+            RegexUnit::UnitChoice(ref list) => {
+                choice_graphs(list.iter().map(|x| x.nfa_graph(case_insensitive)), None)
+            }
+            RegexUnit::ItemList(ref list) => {
+                concat_graphs(list.iter().map(|x| x.build_nfa_graph(case_insensitive)).collect())
+            }
+            RegexUnit::ItemChoice(ref list) => {
+                choice_graphs(list.iter().map(|x| x.build_nfa_graph(case_insensitive)), None)
+            }
+            RegexUnit::CaseInsensitive(ref list) => {
+                choice_graphs(list.iter().map(|x| x.build_nfa_graph(true)), None)
+            }
+            RegexUnit::Capture(index, _, ref list) => {
+                choice_graphs(list.iter().map(|x| x.build_nfa_graph(case_insensitive)), Some(index))
+            }
+        }
+    }
 
-                    start.connect(set![end_id], Some(EdgeMatches::Not(vec![EdgeMatches::Character(c)])));
+    fn collect_capture_names(&self, names: &mut Vec<Option<String>>) {
+        match self {
+            RegexUnit::Capture(index, name, list) => {
+                if names.len() <= *index {
+                    names.resize(index + 1, None);
                 }
+                names[*index] = name.clone();
 
-                graph
+                for x in list {
+                    x.unit.collect_capture_names(names);
+                }
             }
-            &RegexUnit::NotUnits(ref list) => {
-                let mut graph = NFAGraph::new();
-                {
-                    let end_id = graph.end_id();
-                    let (start, _) = graph.nodes_mut();
-
-                    let mut matches = vec![];
-                    for item in list {
-                        match item {
-                            RegexUnit::Character(c) =>
-                                matches.push(EdgeMatches::Character(*c)),
-                            RegexUnit::CharacterRange(s, e) =>
-                                matches.push(EdgeMatches::CharacterRange(*s, *e)),
-                            _ => unimplemented!()
-                        }
-                    }
-
-                    start.connect(set![end_id], Some(EdgeMatches::Not(matches)));
+            RegexUnit::ItemList(list) | RegexUnit::ItemChoice(list) | RegexUnit::CaseInsensitive(list) => {
+                for x in list {
+                    x.unit.collect_capture_names(names);
                 }
-
-                graph
             }
-            &RegexUnit::UnitChoice(ref list) => {
-                let mut sub_graphs = vec![];
-                let mut graph = NFAGraph::new();
-                let end_id = graph.end_id();
-                {
-                    let (start, _) = graph.nodes_mut();
+            _ => {}
+        }
+    }
+}
 
-                    for item in list {
-                        let mut g = item.nfa_graph();
+impl RegexItem {
+    // items built by other modules, like `TransTable::to_regex`
+    pub(crate) fn new(unit: RegexUnit, annotation: RegexAnnotation) -> RegexItem {
+        RegexItem { unit, annotation }
+    }
 
-                        // connect start to sub graph start
-                        start.connect(set![g.start_id()], None);
-                        // connect sub graph to our end
-                        g.end_mut().connect(set![end_id], None);
+    pub fn nfa_graph(&self) -> NFAGraph {
+        self.build_nfa_graph(false)
+    }
 
-                        sub_graphs.push(g);
-                    }
-                }
+    pub(crate) fn into_parts(self) -> (RegexUnit, RegexAnnotation) {
+        (self.unit, self.annotation)
+    }
 
-                // merge sub_graphs to graph
-                for g in sub_graphs {
-                    graph.append_sub_graph(g);
-                }
+    /// names of capture groups by their index, group 0 is the whole match and has no name
+    pub fn capture_names(&self) -> Vec<Option<String>> {
+        let mut names = vec![None];
+        self.unit.collect_capture_names(&mut names);
 
-                graph
-            }
-            &RegexUnit::ItemList(ref list) => {
-                assert!(list.len() > 0);
-                let mut gs: Vec<NFAGraph> = list.iter().map(|x| x.nfa_graph()).collect();
-                let mut graph =
-                    NFAGraph::from_id(gs[0].start_id(), gs.last_mut().unwrap().end_id());
+        names
+    }
 
-                for i in 0..(gs.len() - 1) {
-                    let id = gs[i + 1].start_id();
-                    gs[i].end_mut().connect(set![id], None);
-                }
+    fn build_nfa_graph(&self, case_insensitive: bool) -> NFAGraph {
+        match self.annotation {
+            RegexAnnotation::Repeat(min, max) => self.repeat_nfa_graph(min, max, case_insensitive),
+            _ => annotate_graph(self.unit.nfa_graph(case_insensitive), &self.annotation),
+        }
+    }
 
-                // merge
-                for g in gs {
-                    graph.append_sub_graph(g);
+    // every copy is a fresh sub graph, because node ids are global
+    fn repeat_nfa_graph(&self, min: usize, max: Option<usize>, case_insensitive: bool) -> NFAGraph {
+        let unit_graph = || self.unit.nfa_graph(case_insensitive);
+        let mut gs: Vec<NFAGraph> = (0..min).map(|_| unit_graph()).collect();
+
+        match max {
+            None => match gs.pop() {
+                Some(g) => gs.push(annotate_graph(g, &RegexAnnotation::GreaterZero)),
+                None => gs.push(annotate_graph(unit_graph(), &RegexAnnotation::AnyOccurs)),
+            },
+            Some(max) if max > min => {
+                // nested optional copies `x(x(x)?)?` are less ambiguous than `x?x?x?`
+                let mut g = annotate_graph(unit_graph(), &RegexAnnotation::OneOrZero);
+                for _ in min + 1..max {
+                    g = concat_graphs(vec![unit_graph(), g]);
+                    g = annotate_graph(g, &RegexAnnotation::OneOrZero);
                 }
 
-                graph
+                gs.push(g);
             }
-            &RegexUnit::ItemChoice(ref list) => {
-                let mut sub_graphs = vec![];
-                let mut graph = NFAGraph::new();
-                let end_id = graph.end_id();
-                {
-                    let (start, _) = graph.nodes_mut();
+            Some(_) => {}
+        }
 
-                    for item in list {
-                        let mut g = item.nfa_graph();
+        concat_graphs(gs)
+    }
 
-                        // connect start to sub graph start
-                        start.connect(set![g.start_id()], None);
-                        // connect sub graph to our end
-                        g.end_mut().connect(set![end_id], None);
+    fn nfa_size(&self) -> usize {
+        let copies = match self.annotation {
+            RegexAnnotation::Repeat(min, None) => min.max(1),
+            RegexAnnotation::Repeat(_, Some(0)) => return 2,
+            RegexAnnotation::Repeat(_, Some(max)) => max,
+            _ => 1,
+        };
 
-                        sub_graphs.push(g);
-                    }
-                }
+        self.unit.nfa_size().saturating_mul(copies)
+    }
+}
 
-                // merge sub_graphs to graph
-                for g in sub_graphs {
-                    graph.append_sub_graph(g);
-                }
+fn annotate_graph(mut graph: NFAGraph, annotation: &RegexAnnotation) -> NFAGraph {
+    let end_id = graph.end_id();
+    let start_id = graph.start_id();
 
-                graph
-            }
+    match annotation {
+        RegexAnnotation::OneOrZero => {
+            // `?`
+            graph.start_mut().connect(set![end_id], None);
+        }
+        RegexAnnotation::GreaterZero => {
+            // `+`
+            graph.end_mut().connect(set![start_id], None);
         }
+        RegexAnnotation::AnyOccurs => {
+            // '*'
+            graph.start_mut().connect(set![end_id], None);
+            graph.end_mut().connect(set![start_id], None);
+        }
+        RegexAnnotation::StandAlone | RegexAnnotation::Repeat(..) => {}
     }
+
+    graph
 }
 
-impl RegexItem {
-    pub fn nfa_graph(&self) -> NFAGraph {
-        let mut graph = self.unit.nfa_graph();
+// connect graphs in parallel, sub graphs are generated after the outer nodes.
+// the edges into and out of the sub graphs save the positions of capture group `group`
+fn choice_graphs<I: Iterator<Item = NFAGraph>>(gs: I, group: Option<usize>) -> NFAGraph {
+    let mut graph = NFAGraph::new();
+    let end_id = graph.end_id();
+
+    for mut g in gs {
+        let (open, close) = match group {
+            Some(i) => (Edge::save(set![g.start_id()], i * 2), Edge::save(set![end_id], i * 2 + 1)),
+            None => (Edge::epsilon(set![g.start_id()]), Edge::epsilon(set![end_id])),
+        };
+
+        // connect start to sub graph start
+        graph.start_mut().append_edge(open);
+        // connect sub graph to our end
+        g.end_mut().append_edge(close);
+
+        graph.append_sub_graph(g);
+    }
+
+    graph
+}
+
+// connect graphs one by one, an empty list matches the empty string
+fn concat_graphs(mut gs: Vec<NFAGraph>) -> NFAGraph {
+    if gs.is_empty() {
+        let mut graph = NFAGraph::new();
         let end_id = graph.end_id();
-        let start_id = graph.start_id();
+        graph.start_mut().connect(set![end_id], None);
 
-        match self.annotation {
-            RegexAnnotation::OneOrZero => {
-                // `?`
-                graph.start_mut().connect(set![end_id], None);
-            }
-            RegexAnnotation::GreaterZero => {
-                // `+`
-                graph.end_mut().connect(set![start_id], None);
-            }
-            RegexAnnotation::AnyOccurs => {
-                // '*'
-                graph.start_mut().connect(set![end_id], None);
-                graph.end_mut().connect(set![start_id], None);
-            }
-            RegexAnnotation::StandAlone => {}
+        return graph;
+    }
+
+    let mut graph = NFAGraph::from_id(gs[0].start_id(), gs.last_mut().unwrap().end_id());
+
+    for i in 0..(gs.len() - 1) {
+        let id = gs[i + 1].start_id();
+        gs[i].end_mut().connect(set![id], None);
+    }
+
+    // merge
+    for g in gs {
+        graph.append_sub_graph(g);
+    }
+
+    graph
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RegexParserErrorKind {
+    UnclosedCharacterGroup, // '[' without ']'
+    UnclosedItemGroup,      // '(' without ')'
+    UnopenedItemGroup,      // ')' without '('
+    DanglingAnnotation,     // '?', '+' or '*' without anything to repeat
+    InvalidRange,           // 'x-y' which is not a valid range
+    TrailingEscape,         // '\' at the end of pattern
+    InvalidEscape,          // malformed '\xHH' or '\u{...}'
+    InvalidFlag,            // unknown flag in '(?flags)' or '(?flags:...)'
+    InvalidGroupName,       // malformed '(?P<name>...)'
+    DuplicateGroupName,     // two groups with the same name
+    InvalidRepetition,      // '{n,m}' which is malformed or n > m
+    SizeLimitExceeded,      // pattern expands to too many nfa nodes
+}
+
+impl fmt::Display for RegexParserErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            RegexParserErrorKind::UnclosedCharacterGroup => "unclosed character group",
+            RegexParserErrorKind::UnclosedItemGroup => "unclosed group",
+            RegexParserErrorKind::UnopenedItemGroup => "unopened group",
+            RegexParserErrorKind::DanglingAnnotation => "quantifier without anything to repeat",
+            RegexParserErrorKind::InvalidRange => "invalid character range",
+            RegexParserErrorKind::TrailingEscape => "trailing escape character",
+            RegexParserErrorKind::InvalidEscape => "invalid escape sequence",
+            RegexParserErrorKind::InvalidFlag => "invalid flag",
+            RegexParserErrorKind::InvalidGroupName => "invalid group name",
+            RegexParserErrorKind::DuplicateGroupName => "duplicate group name",
+            RegexParserErrorKind::InvalidRepetition => "invalid repetition",
+            RegexParserErrorKind::SizeLimitExceeded => "pattern exceeds the size limit",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegexParserError {
+    kind: RegexParserErrorKind,
+    offset: usize,
+    pattern: String,
+}
+
+impl RegexParserError {
+    /// `offset` is moved back into the pattern, to the start of the character it falls in
+    pub fn new<T: Into<String>>(kind: RegexParserErrorKind, offset: usize, pattern: T) -> RegexParserError {
+        let pattern = pattern.into();
+        let mut offset = offset.min(pattern.len());
+        while !pattern.is_char_boundary(offset) {
+            offset -= 1;
         }
 
-        graph
+        RegexParserError { kind, offset, pattern }
+    }
+
+    pub fn kind(&self) -> RegexParserErrorKind {
+        self.kind
+    }
+
+    /// byte offset of the offending character in the pattern
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn pattern(&self) -> &str {
+        &self.pattern
     }
 }
 
-type RegexParserError = ();
-type RegexParserResult = Result<RegexItem, RegexParserError>;
+impl fmt::Display for RegexParserError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let column = self.pattern[..self.offset].chars().count();
+
+        writeln!(f, "regex parse error: {} at offset {}", self.kind, self.offset)?;
+        writeln!(f, "    {}", self.pattern)?;
+        write!(f, "    {}^", " ".repeat(column))
+    }
+}
+
+impl Error for RegexParserError {}
+
+pub type RegexParserResult = Result<RegexItem, RegexParserError>;
+
+pub const DEFAULT_SIZE_LIMIT: usize = 1 << 16;
+
+#[derive(Debug, Clone)]
+pub struct RegexBuilder {
+    size_limit: usize,
+    case_insensitive: bool,
+    multi_line: bool,
+}
+
+impl Default for RegexBuilder {
+    fn default() -> RegexBuilder {
+        RegexBuilder::new()
+    }
+}
+
+impl RegexBuilder {
+    pub fn new() -> RegexBuilder {
+        RegexBuilder {
+            size_limit: DEFAULT_SIZE_LIMIT,
+            case_insensitive: false,
+            multi_line: false,
+        }
+    }
+
+    /// same as starting the pattern with `(?i)`
+    pub fn case_insensitive(&mut self, yes: bool) -> &mut RegexBuilder {
+        self.case_insensitive = yes;
+        self
+    }
+
+    /// same as starting the pattern with `(?m)`, `^` and `$` match at line breaks too
+    pub fn multi_line(&mut self, yes: bool) -> &mut RegexBuilder {
+        self.multi_line = yes;
+        self
+    }
+
+    /// max count of nfa nodes the pattern may expand to, mostly consumed by `{n,m}`
+    pub fn size_limit(&mut self, limit: usize) -> &mut RegexBuilder {
+        self.size_limit = limit;
+        self
+    }
+
+    pub fn parse(&self, pattern: &str) -> RegexParserResult {
+        let item = RegexParser::new(pattern, self).parse()?;
+
+        if item.nfa_size() > self.size_limit {
+            return Err(RegexParserError::new(RegexParserErrorKind::SizeLimitExceeded, 0, pattern));
+        }
+
+        Ok(item)
+    }
+}
 
 struct RegexParser<'s> {
-    input: Peekable<Chars<'s>>,
+    pattern: &'s str,
+    input: Peekable<CharIndices<'s>>,
+    size_limit: usize,
+    case_insensitive: bool,
+    multi_line: bool,
+    capture_names: Vec<Option<String>>,
 }
 
 impl<'s> RegexParser<'s> {
+    fn new(pattern: &'s str, builder: &RegexBuilder) -> RegexParser<'s> {
+        RegexParser {
+            pattern,
+            input: pattern.char_indices().peekable(),
+            size_limit: builder.size_limit,
+            case_insensitive: builder.case_insensitive,
+            multi_line: builder.multi_line,
+            capture_names: vec![None],
+        }
+    }
+
     fn parse(&mut self) -> RegexParserResult {
+        let folded = self.case_insensitive;
+        let mut items = self.parse_alternation(folded)?;
+
+        // only an unmatched ')' can stop the alternation
+        if let Some((i, _)) = self.input.next() {
+            return Err(self.error(RegexParserErrorKind::UnopenedItemGroup, i));
+        }
+
+        // top level alternation is the same as it wrapped by parentheses
+        let unit = if folded {
+            RegexUnit::CaseInsensitive(items)
+        } else if items.len() == 1 {
+            return Ok(items.pop().unwrap());
+        } else {
+            RegexUnit::ItemChoice(items)
+        };
+
+        Ok(RegexItem {
+            unit: RegexUnit::ItemList(vec![RegexItem {
+                unit,
+                annotation: RegexAnnotation::StandAlone,
+            }]),
+            annotation: RegexAnnotation::StandAlone,
+        })
+    }
+
+    // alternation := concatenation ('|' concatenation)*
+    // `folded` means the whole alternation is inside of a case insensitive unit already
+    fn parse_alternation(&mut self, folded: bool) -> Result<Vec<RegexItem>, RegexParserError> {
+        let mut items = vec![self.parse_concatenation(folded)?];
+
+        while let Some('|') = self.peek() {
+            self.input.next();
+            items.push(self.parse_concatenation(folded)?);
+        }
+
+        Ok(items)
+    }
+
+    // concatenation := (atom | '(?flags)')*
+    fn parse_concatenation(&mut self, folded: bool) -> RegexParserResult {
         let mut items = vec![];
+        // items since `(?i)` are wrapped into a case insensitive unit
+        let mut fold_from = if self.case_insensitive && !folded { Some(0) } else { None };
 
-        while let Ok(item) = self.dispatch() {
-            items.push(item);
+        loop {
+            match self.peek() {
+                None | Some('|') | Some(')') => break,
+                Some('(') if self.is_flags() => {
+                    let offset = self.expect('(');
+                    self.expect('?');
+                    if self.parse_flags()?.is_empty() {
+                        return Err(self.error(RegexParserErrorKind::InvalidFlag, offset));
+                    }
+                    self.expect(')');
+
+                    if self.case_insensitive && !folded && fold_from.is_none() {
+                        fold_from = Some(items.len());
+                    }
+                }
+                _ => {
+                    let folded = folded || fold_from.is_some();
+                    items.push(self.dispatch(folded)?);
+                }
+            }
         }
 
-        assert_eq!(self.parse_annotation(), RegexAnnotation::StandAlone);
+        if let Some(i) = fold_from {
+            let list = items.split_off(i);
+
+            if !list.is_empty() {
+                items.push(RegexItem {
+                    unit: RegexUnit::CaseInsensitive(vec![RegexItem {
+                        unit: RegexUnit::ItemList(list),
+                        annotation: RegexAnnotation::StandAlone,
+                    }]),
+                    annotation: RegexAnnotation::StandAlone,
+                });
+            }
+        }
 
         Ok(RegexItem {
             unit: RegexUnit::ItemList(items),
@@ -300,179 +715,382 @@ impl<'s> RegexParser<'s> {
         })
     }
 
-    fn dispatch(&mut self) -> RegexParserResult {
-        if let Some(c) = self.input.peek().map(|x| x.clone()) {
+    // looking at `(?flags)`
+    fn is_flags(&self) -> bool {
+        let mut ahead = self.input.clone().map(|(_, c)| c);
+
+        ahead.next() == Some('(')
+            && ahead.next() == Some('?')
+            && ahead.find(|c| !c.is_ascii_alphabetic()) == Some(')')
+    }
+
+    // flags after `(?`, which are applied to the rest of current group
+    fn parse_flags(&mut self) -> Result<String, RegexParserError> {
+        let mut flags = String::new();
+
+        while let Some((i, c)) = self.input.next_if(|&(_, c)| c.is_ascii_alphabetic()) {
             match c {
-                '[' => self.parse_character_group(),
-                '(' => self.parse_item_group(),
-                _ => self.parse_character(),
+                'i' => self.case_insensitive = true,
+                'm' => self.multi_line = true,
+                _ => return Err(self.error(RegexParserErrorKind::InvalidFlag, i)),
             }
-        } else {
-            Err(())
+
+            flags.push(c);
         }
+
+        Ok(flags)
     }
 
-    fn parse_character(&mut self) -> RegexParserResult {
+    fn error(&self, kind: RegexParserErrorKind, offset: usize) -> RegexParserError {
+        RegexParserError::new(kind, offset, self.pattern)
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.input.peek().map(|&(_, c)| c)
+    }
+
+    fn offset(&mut self) -> usize {
+        match self.input.peek() {
+            Some(&(i, _)) => i,
+            None => self.pattern.len(),
+        }
+    }
+
+    fn expect(&mut self, c: char) -> usize {
+        match self.input.next() {
+            Some((i, x)) if x == c => i,
+            _ => unreachable!("expect {:?}", c),
+        }
+    }
 
-        match self.input.peek().map(|x| x.clone()) {
+    fn dispatch(&mut self, folded: bool) -> RegexParserResult {
+        match self.peek() {
+            Some('[') => self.parse_character_group(),
+            Some('(') => self.parse_item_group(folded),
+            Some('?') | Some('+') | Some('*') | Some('{') => {
+                let offset = self.offset();
+                Err(self.error(RegexParserErrorKind::DanglingAnnotation, offset))
+            }
+            _ => self.parse_character(),
+        }
+    }
+
+    fn parse_character(&mut self) -> RegexParserResult {
+        match self.peek() {
             Some('\\') => self.parse_character_escape(),
             Some('.') => {
                 self.input.next();
 
-                Ok(RegexItem {
-                    unit: RegexUnit::NotCharacter(b'\n'),
-                    annotation: self.parse_annotation(),
-                })
+                self.annotate(RegexUnit::NotCharacter('\n'))
+            }
+            Some('^') => {
+                self.input.next();
+
+                let a = if self.multi_line { Assertion::StartLine } else { Assertion::StartText };
+                self.annotate(RegexUnit::Assertion(a))
+            }
+            Some('$') => {
+                self.input.next();
+
+                let a = if self.multi_line { Assertion::EndLine } else { Assertion::EndText };
+                self.annotate(RegexUnit::Assertion(a))
             }
             Some(c) => {
                 self.input.next();
 
-                Ok(RegexItem {
-                    unit: RegexUnit::Character(c as u8),
-                    annotation: self.parse_annotation(),
-                })
+                self.annotate(RegexUnit::Character(c))
             }
-            _ => return Err(())
+            None => unreachable!(),
         }
     }
 
     fn parse_character_escape(&mut self) -> RegexParserResult {
-        assert_eq!(Some('\\'), self.input.next());
+        let offset = self.expect('\\');
+
+        // assertions are not allowed inside of character groups
+        let assertion = match self.peek() {
+            Some('A') => Some(Assertion::StartText),
+            Some('z') => Some(Assertion::EndText),
+            Some('b') => Some(Assertion::WordBoundary),
+            Some('B') => Some(Assertion::NotWordBoundary),
+            _ => None,
+        };
+        if let Some(a) = assertion {
+            self.input.next();
+            return self.annotate(RegexUnit::Assertion(a));
+        }
 
-        match self.input.next() {
-            Some('d') => {
-                Ok(RegexItem {
-                    unit: RegexUnit::CharacterRange(b'0', b'9'),
-                    annotation: self.parse_annotation(),
-                })
+        let unit = self.parse_escape(offset)?;
+        self.annotate(unit)
+    }
+
+    // parse the escape sequence after the '\' at `offset`
+    fn parse_escape(&mut self, offset: usize) -> Result<RegexUnit, RegexParserError> {
+        let c = match self.input.next() {
+            Some((_, c)) => c,
+            None => return Err(self.error(RegexParserErrorKind::TrailingEscape, offset)),
+        };
+
+        if let Some(unit) = perl_class(c) {
+            return Ok(unit);
+        }
+
+        let c = match c {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            'a' => '\x07',
+            'f' => '\x0c',
+            'v' => '\x0b',
+            'x' => self.parse_hex_escape(offset)?,
+            'u' => self.parse_unicode_escape(offset)?,
+            c => c,
+        };
+
+        Ok(RegexUnit::Character(c))
+    }
+
+    // `\xHH`
+    fn parse_hex_escape(&mut self, offset: usize) -> Result<char, RegexParserError> {
+        let mut r = 0;
+
+        for _ in 0..2 {
+            match self.peek().and_then(|c| c.to_digit(16)) {
+                Some(d) => r = r * 16 + d,
+                None => return Err(self.error(RegexParserErrorKind::InvalidEscape, offset)),
             }
-            Some(c) => {
-                Ok(RegexItem {
-                    unit: RegexUnit::Character(c as u8),
-                    annotation: self.parse_annotation(),
-                })
+            self.input.next();
+        }
+
+        Ok(char::from(r as u8))
+    }
+
+    // `\u{H...}`
+    fn parse_unicode_escape(&mut self, offset: usize) -> Result<char, RegexParserError> {
+        let invalid = self.error(RegexParserErrorKind::InvalidEscape, offset);
+
+        if self.input.next().map(|(_, c)| c) != Some('{') {
+            return Err(invalid);
+        }
+
+        let mut r: u32 = 0;
+        let mut digits = 0;
+        while let Some(d) = self.peek().and_then(|c| c.to_digit(16)) {
+            self.input.next();
+            r = r * 16 + d;
+            digits += 1;
+
+            if digits > 6 {
+                return Err(invalid);
             }
-            _ => return Err(()),
+        }
+
+        match self.input.next() {
+            Some((_, '}')) if digits > 0 => char::from_u32(r).ok_or(invalid),
+            _ => Err(invalid),
         }
     }
 
     fn parse_character_group(&mut self) -> RegexParserResult {
-        assert_eq!(Some('['), self.input.next());
+        let start = self.expect('[');
         let mut items = vec![];
         let mut not = false;
 
         // special process for '^'
-        if let Some('^') = self.input.peek() {
+        if let Some('^') = self.peek() {
             self.input.next();
 
             not = true;
         }
 
-        // special process for '-'
-        if let Some('-') = self.input.peek() {
-            self.input.next();
+        // ']' is a literal character if it is the first one in the group, so `[]` is unclosed
+        let mut first = true;
+        loop {
+            if !first && self.peek() == Some(']') {
+                self.input.next();
 
-            items.push(RegexUnit::Character(b'-'));
-        }
+                let unit = if not {
+                    RegexUnit::NotUnits(items)
+                } else {
+                    RegexUnit::UnitChoice(items)
+                };
 
-        loop {
-            match self.input.next().map(|x| x.clone()) {
-                Some('\\') => match self.input.next() {
-                    Some('d') => {
-                        items.push(RegexUnit::CharacterRange(b'0', b'9'));
-                    }
-                    Some(c) => {
-                        items.push(RegexUnit::Character(c as u8));
-                    }
-                    _ => return Err(()),
-                },
-                Some('a') => {
-                    if let Some('-') = self.input.peek() {
-                        self.input.next();
-                        match self.input.next() {
-                            Some('z') => items.push(RegexUnit::CharacterRange(b'a', b'z')),
-                            _ => return Err(()),
-                        }
-                    } else {
-                        items.push(RegexUnit::Character(b'a'));
-                    }
-                }
-                Some('A') => {
-                    if let Some('-') = self.input.peek() {
-                        self.input.next();
-                        match self.input.next() {
-                            Some('Z') => items.push(RegexUnit::CharacterRange(b'A', b'Z')),
-                            _ => return Err(()),
-                        }
-                    } else {
-                        items.push(RegexUnit::Character(b'A'));
-                    }
+                return self.annotate(unit);
+            }
+
+            let offset = self.offset();
+            let unit = self.parse_class_unit(start)?;
+            first = false;
+
+            // '-' is a literal character if it is the last one in the group
+            let mut ahead = self.input.clone();
+            if ahead.next().map(|(_, c)| c) != Some('-') || ahead.peek().map(|&(_, c)| c) == Some(']') {
+                match unit {
+                    RegexUnit::UnitChoice(list) => items.extend(list),
+                    unit => items.push(unit),
                 }
-                Some('0') => {
-                    if let Some('-') = self.input.peek() {
-                        self.input.next();
-                        match self.input.next() {
-                            Some('9') => items.push(RegexUnit::CharacterRange(b'0', b'9')),
-                            _ => return Err(()),
-                        }
-                    } else {
-                        items.push(RegexUnit::Character(b'0'));
-                    }
+                continue;
+            }
+            self.input.next();
+
+            match (unit, self.parse_class_unit(start)?) {
+                (RegexUnit::Character(s), RegexUnit::Character(e)) if s <= e => {
+                    items.push(RegexUnit::CharacterRange(s, e))
                 }
-                Some(']') => {
-                    let unit = if not {
-                        RegexUnit::NotUnits(items)
-                    } else {
-                        RegexUnit::UnitChoice(items)
-                    };
-
-                    return Ok(RegexItem {
-                        unit,
-                        annotation: self.parse_annotation(),
-                    })
+                _ => return Err(self.error(RegexParserErrorKind::InvalidRange, offset)),
+            }
+        }
+    }
+
+    // parse a single character or escape sequence inside `[...]`
+    fn parse_class_unit(&mut self, start: usize) -> Result<RegexUnit, RegexParserError> {
+        match self.input.next() {
+            Some((i, '\\')) => self.parse_escape(i),
+            Some((_, c)) => Ok(RegexUnit::Character(c)),
+            None => Err(self.error(RegexParserErrorKind::UnclosedCharacterGroup, start)),
+        }
+    }
+
+    fn parse_item_group(&mut self, folded: bool) -> RegexParserResult {
+        let start = self.expect('(');
+        // flags are restored at the end of group
+        let case_insensitive = self.case_insensitive;
+        let multi_line = self.multi_line;
+
+        // `(?P<name>...)` or `(?flags:...)`, the other groups are numbered captures
+        let mut flags = String::new();
+        let mut capture = None;
+        if let Some('?') = self.peek() {
+            self.input.next();
+
+            if let Some('P') = self.peek() {
+                capture = Some(self.parse_group_name(start)?);
+            } else {
+                flags = self.parse_flags()?;
+
+                match self.input.next() {
+                    Some((_, ':')) => {}
+                    Some((i, _)) => return Err(self.error(RegexParserErrorKind::InvalidFlag, i)),
+                    None => return Err(self.error(RegexParserErrorKind::UnclosedItemGroup, start)),
                 }
-                Some(c) => {
-                    items.push(RegexUnit::Character(c as u8));
+            }
+        } else {
+            capture = Some(None);
+        }
+
+        // groups are numbered by their opening parentheses
+        let index = capture.map(|name| {
+            self.capture_names.push(name);
+            self.capture_names.len() - 1
+        });
+
+        let fold = flags.contains('i');
+        let items = self.parse_alternation(folded || fold)?;
+
+        match self.input.next() {
+            Some((_, ')')) => {
+                self.case_insensitive = case_insensitive;
+                self.multi_line = multi_line;
+
+                if let Some(i) = index {
+                    let name = self.capture_names[i].clone();
+                    self.annotate(RegexUnit::Capture(i, name, items))
+                } else if let Some(a) = line_anchor(&items).filter(|_| !fold) {
+                    // `(?m:^)` is how a line anchor is printed, so read it back as the anchor itself
+                    self.annotate(RegexUnit::Assertion(a))
+                } else if fold {
+                    self.annotate(RegexUnit::CaseInsensitive(items))
+                } else {
+                    self.annotate(RegexUnit::ItemChoice(items))
                 }
-                None => return Err(()),
             }
+            _ => Err(self.error(RegexParserErrorKind::UnclosedItemGroup, start)),
         }
     }
 
-    fn parse_item_group(&mut self) -> RegexParserResult {
-        assert_eq!(Some('('), self.input.next());
-        let mut items = vec![];
-        let mut buffer = String::new();
+    // `P<name>` after `(?`
+    fn parse_group_name(&mut self, start: usize) -> Result<Option<String>, RegexParserError> {
+        let offset = self.expect('P');
+        let invalid = self.error(RegexParserErrorKind::InvalidGroupName, offset);
 
+        if self.input.next().map(|(_, c)| c) != Some('<') {
+            return Err(invalid);
+        }
+
+        let mut name = String::new();
         loop {
             match self.input.next() {
-                Some(')') => {
-                    items.push((&buffer[..]).into());
-
-                    return Ok(RegexItem {
-                        unit: RegexUnit::ItemChoice(items),
-                        annotation: self.parse_annotation(),
-                    });
-                }
-                Some('|') => {
-                    items.push((&buffer[..]).into());
-                    buffer.clear();
+                Some((_, '>')) if !name.is_empty() => break,
+                Some((_, c)) if c == '_' || c.is_ascii_alphabetic() || (c.is_ascii_digit() && !name.is_empty()) => {
+                    name.push(c)
                 }
-                Some(c) => buffer.push(c),
-                None => return Err(()),
+                Some(_) => return Err(invalid),
+                None => return Err(self.error(RegexParserErrorKind::UnclosedItemGroup, start)),
             }
         }
+
+        if self.capture_names.contains(&Some(name.clone())) {
+            return Err(self.error(RegexParserErrorKind::DuplicateGroupName, offset));
+        }
+
+        Ok(Some(name))
     }
 
-    fn parse_annotation(&mut self) -> RegexAnnotation {
-        let r = match self.input.peek() {
+    fn annotate(&mut self, unit: RegexUnit) -> RegexParserResult {
+        let offset = self.offset();
+        let item = RegexItem {
+            unit,
+            annotation: self.parse_annotation()?,
+        };
+
+        if item.nfa_size() > self.size_limit {
+            return Err(self.error(RegexParserErrorKind::SizeLimitExceeded, offset));
+        }
+
+        Ok(item)
+    }
+
+    fn parse_annotation(&mut self) -> Result<RegexAnnotation, RegexParserError> {
+        let r = match self.peek() {
             Some('?') => RegexAnnotation::OneOrZero,
             Some('+') => RegexAnnotation::GreaterZero,
             Some('*') => RegexAnnotation::AnyOccurs,
-            _ => return RegexAnnotation::StandAlone,
+            Some('{') => return self.parse_repetition(),
+            _ => return Ok(RegexAnnotation::StandAlone),
         };
 
         self.input.next();
+        Ok(r)
+    }
+
+    fn parse_repetition(&mut self) -> Result<RegexAnnotation, RegexParserError> {
+        let offset = self.expect('{');
+        let invalid = self.error(RegexParserErrorKind::InvalidRepetition, offset);
+
+        let min = self.parse_number().ok_or_else(|| invalid.clone())?;
+        let max = match self.input.next() {
+            Some((_, '}')) => return Ok(RegexAnnotation::Repeat(min, Some(min))),
+            Some((_, ',')) if self.peek() == Some('}') => None,
+            Some((_, ',')) => Some(self.parse_number().ok_or_else(|| invalid.clone())?),
+            _ => return Err(invalid),
+        };
+
+        match self.input.next() {
+            Some((_, '}')) if max.is_none_or(|max| min <= max) => Ok(RegexAnnotation::Repeat(min, max)),
+            _ => Err(invalid),
+        }
+    }
+
+    fn parse_number(&mut self) -> Option<usize> {
+        let mut r: Option<usize> = None;
+
+        while let Some(d) = self.peek().and_then(|c| c.to_digit(10)) {
+            self.input.next();
+            r = r.unwrap_or(0).checked_mul(10)?.checked_add(d as usize);
+            r?;
+        }
+
         r
     }
 }
@@ -480,6 +1098,7 @@ impl<'s> RegexParser<'s> {
 #[cfg(test)]
 mod test {
 
+    use execute_engine::*;
     use regex_gen::*;
     use transtable::*;
 
@@ -544,4 +1163,413 @@ mod test {
         let r: RegexItem = s.into();
         assert_eq!(r.to_string(), s);
     }
+    #[test]
+    fn test_parse_character_range() {
+        let r: RegexItem = r#"[a-f0-7]"#.into();
+        assert_eq!(r.unit, RegexUnit::ItemList(vec![RegexItem {
+            unit: RegexUnit::UnitChoice(vec![
+                RegexUnit::CharacterRange('a', 'f'),
+                RegexUnit::CharacterRange('0', '7'),
+            ]),
+            annotation: RegexAnnotation::StandAlone,
+        }]));
+
+        let r1: RegexItem = r#"[\--\]!-~-]"#.into();
+        let r2: RegexItem = r#"[\--\]\!-\~\-]"#.into();
+        assert_eq!(r1, r2);
+        assert_eq!(r1.unit, RegexUnit::ItemList(vec![RegexItem {
+            unit: RegexUnit::UnitChoice(vec![
+                RegexUnit::CharacterRange('-', ']'),
+                RegexUnit::CharacterRange('!', '~'),
+                RegexUnit::Character('-'),
+            ]),
+            annotation: RegexAnnotation::StandAlone,
+        }]));
+
+        let r: RegexItem = r#"[--/]"#.into();
+        let mut t = TransTable::from_nfa(&r.nfa_graph());
+        t.as_dfa();
+        let ee = ExecuteEngine::with_transtable(t);
+        assert!(ee.exact_match("-"));
+        assert!(ee.exact_match("."));
+        assert!(ee.exact_match("/"));
+        assert!(!ee.exact_match("0"));
+
+        // a leading ']' is a member of the group
+        let r: RegexItem = r#"[]a]"#.into();
+        let ee = ExecuteEngine::new(&r);
+        assert!(ee.exact_match("]"));
+        assert!(ee.exact_match("a"));
+        assert_eq!(r.to_string(), r#"[\]a]"#);
+        let r: RegexItem = r#"[^]]"#.into();
+        assert_eq!(r, r#"[^\]]"#.into());
+
+        let e = "[]a".parse::<RegexItem>().unwrap_err();
+        assert_eq!(e.kind(), RegexParserErrorKind::UnclosedCharacterGroup);
+        assert_eq!(e.offset(), 0);
+        let e = "x[^]".parse::<RegexItem>().unwrap_err();
+        assert_eq!(e.kind(), RegexParserErrorKind::UnclosedCharacterGroup);
+        assert_eq!(e.offset(), 1);
+    }
+
+    #[test]
+    fn test_parse_nested_group() {
+        fn list(items: Vec<RegexItem>) -> RegexItem {
+            RegexItem { unit: RegexUnit::ItemList(items), annotation: RegexAnnotation::StandAlone }
+        }
+        fn choice(items: Vec<RegexItem>) -> RegexItem {
+            RegexItem { unit: RegexUnit::ItemChoice(items), annotation: RegexAnnotation::StandAlone }
+        }
+        fn group(i: usize, items: Vec<RegexItem>) -> RegexItem {
+            RegexItem { unit: RegexUnit::Capture(i, None, items), annotation: RegexAnnotation::StandAlone }
+        }
+        fn ch(c: char) -> RegexItem {
+            RegexItem { unit: RegexUnit::Character(c), annotation: RegexAnnotation::StandAlone }
+        }
+
+        let r: RegexItem = r#"(a(b|c)d)"#.into();
+        assert_eq!(r, list(vec![group(1, vec![list(vec![
+            ch('a'),
+            group(2, vec![list(vec![ch('b')]), list(vec![ch('c')])]),
+            ch('d'),
+        ])])]));
+        assert_eq!(r.to_string(), "(a(b|c)d)");
+
+        let r: RegexItem = r#"(?:a(?:b|c))"#.into();
+        assert_eq!(r, list(vec![choice(vec![list(vec![
+            ch('a'),
+            choice(vec![list(vec![ch('b')]), list(vec![ch('c')])]),
+        ])])]));
+        assert_eq!(r.to_string(), "(?:a(?:b|c))");
+
+        let r: RegexItem = r#"([|])"#.into();
+        assert_eq!(r, list(vec![group(1, vec![list(vec![RegexItem {
+            unit: RegexUnit::UnitChoice(vec![RegexUnit::Character('|')]),
+            annotation: RegexAnnotation::StandAlone,
+        }])])]));
+
+        let r: RegexItem = r#"(a\||\))"#.into();
+        assert_eq!(r, list(vec![group(1, vec![list(vec![ch('a'), ch('|')]), list(vec![ch(')')])])]));
+
+        let r1: RegexItem = r#"foo|bar|"#.into();
+        let r2: RegexItem = r#"(?:foo|bar|)"#.into();
+        assert_eq!(r1, r2);
+
+        let mut t = TransTable::from_nfa(&r1.nfa_graph());
+        t.as_dfa();
+        let ee = ExecuteEngine::with_transtable(t);
+        assert!(ee.exact_match("foo"));
+        assert!(ee.exact_match("bar"));
+        assert!(ee.exact_match(""));
+        assert!(!ee.exact_match("fo"));
+
+        let r: RegexItem = r#"x((a|b)+c|d)*"#.into();
+        let mut t = TransTable::from_nfa(&r.nfa_graph());
+        t.as_dfa();
+        let ee = ExecuteEngine::with_transtable(t);
+        assert!(ee.exact_match("x"));
+        assert!(ee.exact_match("xabcd"));
+        assert!(ee.exact_match("xdbbac"));
+        assert!(!ee.exact_match("xab"));
+
+        let e = "(a(b)".parse::<RegexItem>().unwrap_err();
+        assert_eq!(e.kind(), RegexParserErrorKind::UnclosedItemGroup);
+        assert_eq!(e.offset(), 0);
+
+        let e = "a|b)c".parse::<RegexItem>().unwrap_err();
+        assert_eq!(e.kind(), RegexParserErrorKind::UnopenedItemGroup);
+        assert_eq!(e.offset(), 3);
+    }
+
+    #[test]
+    fn test_parse_capture() {
+        for s in &[r#"(a)(?:b)(c)"#, r#"(?P<year>\d{4})-(?P<month>\d\d)"#, r#"((a)|(?P<x_1>b))+"#] {
+            let r: RegexItem = (*s).into();
+            assert_eq!(r.to_string(), *s);
+        }
+
+        let r: RegexItem = r#"(a(?P<b>b)(?:c)|(d))(?i:e)"#.into();
+        assert_eq!(r.capture_names(), vec![None, None, Some("b".to_string()), None]);
+        let r: RegexItem = r#"a|b"#.into();
+        assert_eq!(r.capture_names(), vec![None]);
+
+        let e = "(?P<a>x)(?P<a>y)".parse::<RegexItem>().unwrap_err();
+        assert_eq!(e.kind(), RegexParserErrorKind::DuplicateGroupName);
+        assert_eq!(e.offset(), 10);
+        for s in &["(?P<>x)", "(?P<1a>x)", "(?P<a-b>x)", "(?Pa>x)"] {
+            let e = s.parse::<RegexItem>().unwrap_err();
+            assert_eq!(e.kind(), RegexParserErrorKind::InvalidGroupName);
+            assert_eq!(e.offset(), 2);
+        }
+        let e = "(?P<ab".parse::<RegexItem>().unwrap_err();
+        assert_eq!(e.kind(), RegexParserErrorKind::UnclosedItemGroup);
+        assert_eq!(e.offset(), 0);
+    }
+
+    #[test]
+    fn test_parse_perl_class() {
+        for s in &[r#"\w+"#, r#"\s\S*"#, r#"\d\D?"#, r#"[\W0-9]"#, r#"[^\S]"#] {
+            let r: RegexItem = (*s).into();
+            assert_eq!(r.to_string(), *s);
+        }
+
+        let r1: RegexItem = r#"[\w-]"#.into();
+        let r2: RegexItem = r#"[0-9A-Za-z_\-]"#.into();
+        assert_eq!(r1, r2);
+
+        let r: RegexItem = r#"\w+\s*=\s*\S+"#.into();
+        let mut t = TransTable::from_nfa(&r.nfa_graph());
+        t.as_dfa();
+        let ee = ExecuteEngine::with_transtable(t);
+        assert!(ee.exact_match("key = value"));
+        assert!(ee.exact_match("some_key\t=\"v\""));
+        assert!(!ee.exact_match("some key = v"));
+        assert!(!ee.exact_match(" = v"));
+
+        let r: RegexItem = r#"[^\W\d]\W"#.into();
+        let mut t = TransTable::from_nfa(&r.nfa_graph());
+        t.as_dfa();
+        let ee = ExecuteEngine::with_transtable(t);
+        assert!(ee.exact_match("a-"));
+        assert!(ee.exact_match("_ "));
+        assert!(!ee.exact_match("0-"));
+        assert!(!ee.exact_match("--"));
+        assert!(!ee.exact_match("ab"));
+
+        let r: RegexItem = r#"\D[\s,]"#.into();
+        let mut t = TransTable::from_nfa(&r.nfa_graph());
+        t.as_dfa();
+        let ee = ExecuteEngine::with_transtable(t);
+        assert!(ee.exact_match("a,"));
+        assert!(ee.exact_match("a\n"));
+        assert!(!ee.exact_match("1,"));
+        assert!(!ee.exact_match("aa"));
+    }
+
+    #[test]
+    fn test_parse_escape() {
+        let r1: RegexItem = r#"\n\t\r\0\x41\u{42}[\x30-\u{39}\n]"#.into();
+        let r2: RegexItem = "\n\t\r\0AB[0-9\n]".into();
+        assert_eq!(r1, r2);
+        assert_eq!(r1.to_string(), r#"\n\t\r\0AB[0-9\n]"#);
+
+        let r: RegexItem = "a\\u{7}\\x7f[\\x01-\\x1f]".into();
+        assert_eq!(r.to_string(), r#"a\x07\x7F[\x01-\x1F]"#);
+
+        let s = r#"\(\)\|\.\*\+\?\{\}\[\]\^\$\\[\^\]\-\[]"#;
+        let r: RegexItem = s.into();
+        assert_eq!(r.to_string(), s);
+
+        // printed items parse back to the same item
+        let units = vec![
+            RegexUnit::Character('{'),
+            RegexUnit::Character('\u{80}'),
+            RegexUnit::Character('\u{2028}'),
+            RegexUnit::Character('é'),
+            RegexUnit::UnitChoice(vec![RegexUnit::Character('^'), RegexUnit::CharacterRange('[', ']')]),
+            RegexUnit::NotUnits(vec![RegexUnit::Character('\t'), RegexUnit::Character('-')]),
+        ];
+        for unit in units {
+            let item = RegexItem {
+                unit: RegexUnit::ItemList(vec![RegexItem {
+                    unit,
+                    annotation: RegexAnnotation::GreaterZero,
+                }]),
+                annotation: RegexAnnotation::StandAlone,
+            };
+            assert_eq!(item, item.to_string().parse().unwrap());
+        }
+
+        for s in &[r#"\x4"#, r#"\xg0"#, r#"[\u{D800}]"#, r#"\u41"#, r#"\u{}"#, r#"\u{1234567}"#, r#"\u{110000}"#] {
+            let e = s.parse::<RegexItem>().unwrap_err();
+            assert_eq!(e.kind(), RegexParserErrorKind::InvalidEscape);
+            assert_eq!(e.offset(), s.find('\\').unwrap());
+        }
+    }
+
+    #[test]
+    fn test_parse_case_insensitive() {
+        let r: RegexItem = r#"(?i)select"#.into();
+        assert_eq!(r.to_string(), "(?i:select)");
+        let r: RegexItem = r#"a(?i)b|c"#.into();
+        assert_eq!(r.to_string(), "(?:a(?i:b)|(?i:c))");
+        let r: RegexItem = r#"(?i)(a|b)+(?i:c)"#.into();
+        assert_eq!(r.to_string(), "(?i:(a|b)+(?i:c))");
+        let r = RegexBuilder::new().case_insensitive(true).parse("ab|c").unwrap();
+        assert_eq!(r.to_string(), "(?i:ab|c)");
+
+        for s in &["(?i:select)", "a(?i:b|c)+d", "(x(?i:y))"] {
+            let r: RegexItem = (*s).into();
+            assert_eq!(r.to_string(), *s);
+        }
+
+        let r: RegexItem = r#"(?i)select\s+[a-c_]+"#.into();
+        let mut t = TransTable::from_nfa(&r.nfa_graph());
+        t.as_dfa();
+        let ee = ExecuteEngine::with_transtable(t);
+        assert!(ee.exact_match("select ab_c"));
+        assert!(ee.exact_match("SELECT CAB"));
+        assert!(ee.exact_match("SeLeCt\tB"));
+        assert!(!ee.exact_match("SELECT D"));
+
+        let r: RegexItem = r#"a(?i:b)c"#.into();
+        let mut t = TransTable::from_nfa(&r.nfa_graph());
+        t.as_dfa();
+        let ee = ExecuteEngine::with_transtable(t);
+        assert!(ee.exact_match("aBc"));
+        assert!(ee.exact_match("abc"));
+        assert!(!ee.exact_match("ABc"));
+        assert!(!ee.exact_match("abC"));
+
+        let r = RegexBuilder::new().case_insensitive(true).parse("[^a-cé]σ").unwrap();
+        let mut t = TransTable::from_nfa(&r.nfa_graph());
+        t.as_dfa();
+        let ee = ExecuteEngine::with_transtable(t);
+        assert!(ee.exact_match("dΣ"));
+        assert!(ee.exact_match("Dσ"));
+        assert!(!ee.exact_match("Bσ"));
+        assert!(!ee.exact_match("Éσ"));
+
+        let r = RegexBuilder::new().case_insensitive(true).parse(r"[\s\S]").unwrap();
+        let mut t = TransTable::from_nfa(&r.nfa_graph());
+        t.as_dfa();
+        let ee = ExecuteEngine::with_transtable(t);
+        assert!(ee.exact_match("Σ"));
+
+        let e = "(?x)a".parse::<RegexItem>().unwrap_err();
+        assert_eq!(e.kind(), RegexParserErrorKind::InvalidFlag);
+        assert_eq!(e.offset(), 2);
+        let e = "a(?)".parse::<RegexItem>().unwrap_err();
+        assert_eq!(e.kind(), RegexParserErrorKind::InvalidFlag);
+        assert_eq!(e.offset(), 1);
+        let e = "(?i-a)".parse::<RegexItem>().unwrap_err();
+        assert_eq!(e.kind(), RegexParserErrorKind::InvalidFlag);
+        assert_eq!(e.offset(), 3);
+    }
+
+    #[test]
+    fn test_parse_assertion() {
+        for s in &[r#"^abc$"#, r#"\bfoo\B"#, r#"a\$\^"#, r#"[$\^]"#, r#"(^|,)x"#] {
+            let r: RegexItem = (*s).into();
+            assert_eq!(r.to_string(), *s);
+        }
+
+        let r1: RegexItem = r#"\Aab\z"#.into();
+        let r2: RegexItem = r#"^ab$"#.into();
+        assert_eq!(r1, r2);
+
+        let r: RegexItem = r#"(?m)^a$"#.into();
+        assert_eq!(r.to_string(), "(?m:^)a(?m:$)");
+        assert_eq!(r, r.to_string().parse().unwrap());
+        let r: RegexItem = r#"(?m:^)a$"#.into();
+        assert_eq!(r.to_string(), "(?m:^)a$");
+        assert_eq!(r, r.to_string().parse().unwrap());
+        let r = RegexBuilder::new().multi_line(true).parse("^a|(?:$)+").unwrap();
+        assert_eq!(r.to_string(), "(?:(?m:^)a|(?m:$)+)");
+        assert_eq!(r, r.to_string().parse().unwrap());
+        // other groups around an anchor are kept
+        let r: RegexItem = r#"(?m:^a)"#.into();
+        assert_eq!(r.to_string(), "(?:(?m:^)a)");
+        assert_eq!(r, r.to_string().parse().unwrap());
+
+        let r: RegexItem = r#"[\b]"#.into();
+        assert_eq!(r.to_string(), "[b]");
+    }
+
+    #[test]
+    fn test_parse_repetition() {
+        for s in &[r#"a{3}"#, r#"a{2,}"#, r#"(ab|c){0,4}"#, r#"[a-f]{1,2}b?"#] {
+            let r: RegexItem = (*s).into();
+            assert_eq!(r.to_string(), *s);
+        }
+
+        let r: RegexItem = r#"a{2,5}"#.into();
+        let t = TransTable::from_nfa(&r.nfa_graph());
+        assert_eq!(t.state_count(), 10);
+
+        let r: RegexItem = r#"\d{4}-\d{2}-\d{2}"#.into();
+        let mut t = TransTable::from_nfa(&r.nfa_graph());
+        t.as_dfa();
+        let ee = ExecuteEngine::with_transtable(t);
+        assert!(ee.exact_match("2018-07-26"));
+        assert!(!ee.exact_match("2018-7-26"));
+        assert!(!ee.exact_match("20180-07-26"));
+
+        let r: RegexItem = r#"a{2,}b{0,2}c{0}"#.into();
+        let mut t = TransTable::from_nfa(&r.nfa_graph());
+        t.as_dfa();
+        let ee = ExecuteEngine::with_transtable(t);
+        assert!(!ee.exact_match("a"));
+        assert!(ee.exact_match("aa"));
+        assert!(ee.exact_match("aaaab"));
+        assert!(ee.exact_match("aaabb"));
+        assert!(!ee.exact_match("aabbb"));
+        assert!(!ee.exact_match("aac"));
+
+        let e = "a{3,2}".parse::<RegexItem>().unwrap_err();
+        assert_eq!(e.kind(), RegexParserErrorKind::InvalidRepetition);
+        assert_eq!(e.offset(), 1);
+        for s in &["a{", "a{,3}", "a{1,x}", "a{99999999999999999999}"] {
+            let e = s.parse::<RegexItem>().unwrap_err();
+            assert_eq!(e.kind(), RegexParserErrorKind::InvalidRepetition);
+        }
+
+        let e = "ab{100000}".parse::<RegexItem>().unwrap_err();
+        assert_eq!(e.kind(), RegexParserErrorKind::SizeLimitExceeded);
+        assert_eq!(e.offset(), 2);
+
+        let e = "(a{100}){1000}".parse::<RegexItem>().unwrap_err();
+        assert_eq!(e.kind(), RegexParserErrorKind::SizeLimitExceeded);
+        assert_eq!(e.offset(), 8);
+
+        assert!(RegexBuilder::new().size_limit(100).parse("a{50}").is_ok());
+        let e = RegexBuilder::new().size_limit(100).parse("a{30}b{30}").unwrap_err();
+        assert_eq!(e.kind(), RegexParserErrorKind::SizeLimitExceeded);
+        assert_eq!(e.offset(), 0);
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let e = "ab[cd".parse::<RegexItem>().unwrap_err();
+        assert_eq!(e.kind(), RegexParserErrorKind::UnclosedCharacterGroup);
+        assert_eq!(e.offset(), 2);
+        assert_eq!(e.to_string(), "regex parse error: unclosed character group at offset 2\n    ab[cd\n      ^");
+
+        let e = "a(b|c".parse::<RegexItem>().unwrap_err();
+        assert_eq!(e.kind(), RegexParserErrorKind::UnclosedItemGroup);
+        assert_eq!(e.offset(), 1);
+
+        let e = "a(b|*c)".parse::<RegexItem>().unwrap_err();
+        assert_eq!(e.kind(), RegexParserErrorKind::DanglingAnnotation);
+        assert_eq!(e.offset(), 4);
+
+        let e = "a+*".parse::<RegexItem>().unwrap_err();
+        assert_eq!(e.kind(), RegexParserErrorKind::DanglingAnnotation);
+        assert_eq!(e.offset(), 2);
+
+        let e = "a[z-a]".parse::<RegexItem>().unwrap_err();
+        assert_eq!(e.kind(), RegexParserErrorKind::InvalidRange);
+        assert_eq!(e.offset(), 2);
+
+        let e = "[0-\\d]".parse::<RegexItem>().unwrap_err();
+        assert_eq!(e.kind(), RegexParserErrorKind::InvalidRange);
+        assert_eq!(e.offset(), 1);
+
+        let e = "[a-".parse::<RegexItem>().unwrap_err();
+        assert_eq!(e.kind(), RegexParserErrorKind::UnclosedCharacterGroup);
+        assert_eq!(e.offset(), 0);
+
+        let e = "é\\".parse::<RegexItem>().unwrap_err();
+        assert_eq!(e.kind(), RegexParserErrorKind::TrailingEscape);
+        assert_eq!(e.offset(), 2);
+        assert!(e.to_string().ends_with("\n    é\\\n     ^"));
+
+        // offsets of errors made by callers are kept inside the pattern
+        let e = RegexParserError::new(RegexParserErrorKind::TrailingEscape, 1, "é");
+        assert_eq!(e.offset(), 0);
+        assert!(e.to_string().ends_with("\n    é\n    ^"));
+        let e = RegexParserError::new(RegexParserErrorKind::TrailingEscape, 9, "ab");
+        assert_eq!(e.offset(), 2);
+        assert!(e.to_string().ends_with("\n    ab\n      ^"));
+    }
 }
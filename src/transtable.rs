@@ -1,4 +1,4 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt;
 
 use itertools::*;
@@ -13,17 +13,19 @@ fn append_states(table: &mut TransTable, nfa: &NFAGraph) {
     }
 }
 
+// sub graphs first, the nodes of a graph built by `concat_graphs` share ids
+// with nodes of its sub graphs, and their edges are added later.
 fn append_trans(table: &mut TransTable, nfa: &NFAGraph) {
+    for n in nfa.sub_graphs() {
+        append_trans(table, n);
+    }
+
     let (start, end) = nfa.nodes();
     let start_id = nfa.start_id();
     let end_id = nfa.end_id();
 
     table.append_edges(&set![start_id], &mut start.edges().clone());
     table.append_edges(&set![end_id], &mut end.edges().clone());
-
-    for n in nfa.sub_graphs() {
-        append_trans(table, n);
-    }
 }
 
 pub struct TransTable {
@@ -31,6 +33,11 @@ pub struct TransTable {
     end: HashSet<States>,
     states: HashSet<States>,
     trans: HashMap<States, Vec<Edge>>,
+
+    // only tables with assertions depend on the characters around a match,
+    // `start` and `end` are the ones for the start and end of input.
+    context_start: HashMap<Context, States>,
+    context_end: HashMap<Context, HashSet<States>>,
 }
 
 impl TransTable {
@@ -40,19 +47,148 @@ impl TransTable {
             end: HashSet::new(),
             states: HashSet::new(),
             trans: HashMap::new(),
+            context_start: HashMap::new(),
+            context_end: HashMap::new(),
         };
 
         r.end.insert(set![nfa.end_id()]);
         append_states(&mut r, nfa);
         append_trans(&mut r, nfa);
 
+        if r.trans.values().any(|edges| edges.iter().any(|e| e.assertion().is_some())) {
+            r.resolve_assertions();
+        }
+
         r
     }
 
+    // split every state by the context before it and the contexts allowed after it,
+    // so look-around edges become epsilon edges or disappear and characters
+    // edges are split by the context they lead to.
+    fn resolve_assertions(&mut self) {
+        type Key = (States, Context, u8);
+
+        let mut ids: HashMap<Key, States> = HashMap::new();
+        let mut visit: Vec<Key> = vec![];
+        let mut id_of = |key: Key, visit: &mut Vec<Key>| {
+            let next = set![ids.len()];
+            ids.entry(key.clone())
+                .or_insert_with(|| {
+                    visit.push(key);
+                    next
+                })
+                .clone()
+        };
+
+        let old_end = self.end.iter().next().unwrap().clone();
+        let starts: Vec<(Context, States)> = CONTEXTS
+            .iter()
+            .map(|&ctx| (ctx, id_of((self.start.clone(), ctx, ALL_CONTEXTS), &mut visit)))
+            .collect();
+        let mut trans: HashMap<States, Vec<Edge>> = HashMap::new();
+        let mut accepts = vec![];
+
+        while let Some((state, prev, mask)) = visit.pop() {
+            let id = id_of((state.clone(), prev, mask), &mut visit);
+            let mut edges = vec![];
+
+            for e in &self.trans[&state] {
+                let next = e.next_node().clone();
+
+                match (e.assertion(), e.matches()) {
+                    (Some(a), _) => {
+                        let mask = mask & a.next_mask(prev);
+                        if mask != 0 {
+                            edges.push(Edge::epsilon(id_of((next, prev, mask), &mut visit)));
+                        }
+                    }
+                    (None, None) => edges.push(e.redirect(id_of((next, prev, mask), &mut visit))),
+                    (None, Some(m)) => {
+                        let ranges = m.ranges();
+
+                        for ctx in &[Context::Newline, Context::Word, Context::Other] {
+                            if mask & ctx.mask() == 0 {
+                                continue;
+                            }
+
+                            if let Some(m) = EdgeMatches::from_ranges(&intersect_ranges(&ranges, &ctx.ranges())) {
+                                let dest = id_of((next.clone(), *ctx, ALL_CONTEXTS), &mut visit);
+                                edges.push(Edge::new(dest, Some(m)));
+                            }
+                        }
+                    }
+                }
+            }
+
+            if state == old_end {
+                accepts.push((id.clone(), mask));
+            }
+
+            trans.insert(id, edges);
+        }
+
+        // accepting states lead to a single end state for each context after the match
+        let ends: Vec<(Context, States)> = CONTEXTS
+            .iter()
+            .enumerate()
+            .map(|(i, &ctx)| (ctx, set![ids.len() + i]))
+            .collect();
+        for (id, mask) in accepts {
+            for (ctx, end) in &ends {
+                if mask & ctx.mask() != 0 {
+                    trans.get_mut(&id).unwrap().push(Edge::epsilon(end.clone()));
+                }
+            }
+        }
+
+        self.start = starts[0].1.clone();
+        self.context_start = starts.into_iter().collect();
+        self.context_end = HashMap::new();
+        for (ctx, end) in ends {
+            trans.insert(end.clone(), vec![]);
+            self.context_end.entry(ctx).or_default().insert(end);
+        }
+        self.end = self.context_end.remove(&Context::Text).unwrap();
+        self.states = trans.keys().cloned().collect();
+        self.trans = trans;
+    }
+
+    // a dfa made by other modules, states are collected from `trans`
+    pub(crate) fn from_parts(
+        start: States,
+        end: HashSet<States>,
+        trans: HashMap<States, Vec<Edge>>,
+        context_start: HashMap<Context, States>,
+        context_end: HashMap<Context, HashSet<States>>,
+    ) -> TransTable {
+        TransTable {
+            start,
+            end,
+            states: trans.keys().cloned().collect(),
+            trans,
+            context_start,
+            context_end,
+        }
+    }
+
     pub fn start_id(&self) -> &States {
         &self.start
     }
 
+    // start state for a match which is preceded by `prev`
+    pub fn start_after(&self, prev: Option<char>) -> &States {
+        self.start_in(Context::of(prev))
+    }
+
+    pub(crate) fn start_in(&self, ctx: Context) -> &States {
+        self.context_start.get(&ctx).unwrap_or(&self.start)
+    }
+
+    // if the table is made of assertions which depend on the characters around a match
+    pub(crate) fn is_context_dependent(&self) -> bool {
+        !self.context_start.is_empty()
+    }
+
     pub fn state_count(&self) -> usize {
         self.states.len()
     }
@@ -65,77 +201,300 @@ impl TransTable {
         &self.end
     }
 
+    // if `state` accepts a match which is followed by `next`
+    pub fn is_end_before(&self, state: &States, next: Option<char>) -> bool {
+        self.is_end_in(state, Context::of(next))
+    }
+
+    pub(crate) fn is_end_in(&self, state: &States, ctx: Context) -> bool {
+        match self.context_end.get(&ctx) {
+            Some(end) => end.contains(state),
+            None => self.end.contains(state),
+        }
+    }
+
+    // the state after reading `c`, the table must be a dfa
+    pub(crate) fn next_state(&self, state: &States, c: char) -> Option<&States> {
+        self.trans[state].iter().find(|x| x.match_character(c)).map(|x| x.next_node())
+    }
+
+    // if the dfa reads the whole string from start and stops at an end
+    pub(crate) fn accepts(&self, s: &str) -> bool {
+        let mut state = &self.start;
+        for c in s.chars() {
+            state = match self.next_state(state, c) {
+                Some(x) => x,
+                None => return false,
+            };
+        }
+
+        self.end.contains(state)
+    }
+
     pub fn trans_map(&self) -> &HashMap<States, Vec<Edge>> {
         &self.trans
     }
 
+    // subset construction, a state of the dfa is the union of the nfa states it may be in.
+    // edges of every state are split into disjoint classes of characters, so at most one
+    // edge matches any character.
     pub fn as_dfa(&mut self) {
-        // mark epsilon move as end state
-        {
-            // collect state epsilon move
-            let epsilon_move: Vec<(States, HashSet<States>)> = self
-                .states
-                .iter()
-                .map(|x| (x.clone(), self.epsilon_move(x)))
-                .collect();
+        // make sure every state is a single id, even if the table is a dfa already
+        self.reset_state_mark();
+
+        let start = self.closure(Some(&self.start));
+        let mut visit = vec![start.clone()];
+        let context_start: HashMap<Context, States> = self
+            .context_start
+            .iter()
+            .map(|(&ctx, x)| (ctx, self.closure(Some(x))))
+            .collect();
+        visit.extend(context_start.values().cloned());
+
+        let mut trans: HashMap<States, Vec<Edge>> = HashMap::new();
+        while let Some(state) = visit.pop() {
+            if trans.contains_key(&state) {
+                continue;
+            }
+
+            let edges = self.dfa_edges(&state);
+            visit.extend(edges.iter().map(|x| x.next_node().clone()));
+            trans.insert(state, edges);
+        }
+
+        let ends = |end: &HashSet<States>| -> HashSet<States> {
+            trans
+                .keys()
+                .filter(|x| x.iter().any(|&id| end.contains(&set![id])))
+                .cloned()
+                .collect()
+        };
+        self.end = ends(&self.end);
+        self.context_end = self.context_end.iter().map(|(&ctx, x)| (ctx, ends(x))).collect();
+
+        self.start = start;
+        self.context_start = context_start;
+        self.states = trans.keys().cloned().collect();
+        self.trans = trans;
+    }
+
+    // merge states which accept the same language by Hopcroft's partition refinement,
+    // the table must be a dfa already, see `as_dfa`.
+    pub fn minimize(&mut self) {
+        let mut states: Vec<States> = self.states.iter().cloned().collect();
+        states.sort();
+        let index: HashMap<&States, usize> = states.iter().enumerate().map(|(i, x)| (x, i)).collect();
 
-            assert!(self.end.len() == 1);
-            let end = self.end.iter().next().unwrap().clone();
+        // an explicit dead state makes the table complete
+        let dead = states.len();
+        let count = dead + 1;
 
-            for (state, dests) in epsilon_move {
-                if dests.contains(&end) {
-                    self.end.insert(state);
+        // disjoint classes of characters, every class starts at a boundary
+        let mut bounds: Vec<char> = self
+            .trans
+            .values()
+            .flat_map(|x| x.iter())
+            .flat_map(|e| e.matches().as_ref().map_or(vec![], |x| x.ranges()))
+            .flat_map(|(s, e)| Some(s).into_iter().chain(char_after(e)))
+            .collect();
+        bounds.sort();
+        bounds.dedup();
+
+        let mut delta = vec![vec![dead; bounds.len()]; count];
+        for (q, state) in states.iter().enumerate() {
+            for e in &self.trans[state] {
+                let dest = index[e.next_node()];
+
+                for (s, e) in e.matches().as_ref().map_or(vec![], |x| x.ranges()) {
+                    let first = bounds.binary_search(&s).unwrap();
+                    for c in (first..bounds.len()).take_while(|&c| bounds[c] <= e) {
+                        delta[q][c] = dest;
+                    }
                 }
             }
         }
 
-        // generate edges
-        for (state, mut edges) in self
-            .states
-            .iter()
-            .filter(|&x| self.has_epsilon_edge(&*x))
-            .map(|x| (x.clone(), self.posssible_nontrivial_edges(&*x)))
-            .collect::<Vec<(States, Vec<Edge>)>>()
-        {
-            self.append_edges(&state, &mut edges);
+        let mut inverse = vec![vec![vec![]; count]; bounds.len()];
+        for (q, dests) in delta.iter().enumerate() {
+            for (c, &dest) in dests.iter().enumerate() {
+                inverse[c][dest].push(q);
+            }
         }
 
-        // collect all useful states
-        let mut useful_states = vec![self.start.clone()];
-        let mut visit = vec![self.start.clone()];
-        while let Some(state) = visit.pop() {
-            for e in self.trans.get(&state).unwrap() {
-                let n = e.next_node();
-                if !useful_states.contains(&n) && e.matches().is_some() {
-                    useful_states.push(n.clone());
-                    visit.push(n.clone());
+        // states are distinguished by the contexts after the match they accept in
+        let signature = |q: usize| -> u8 {
+            if q == dead {
+                return 0;
+            }
+
+            let mut r = if self.end.contains(&states[q]) { Context::Text.mask() } else { 0 };
+            for (ctx, end) in &self.context_end {
+                if end.contains(&states[q]) {
+                    r |= ctx.mask();
+                }
+            }
+
+            r
+        };
+
+        let mut initial: BTreeMap<u8, Vec<usize>> = BTreeMap::new();
+        for q in 0..count {
+            initial.entry(signature(q)).or_default().push(q);
+        }
+
+        let mut blocks: Vec<Vec<usize>> = initial.into_values().collect();
+        let mut block_of = vec![0; count];
+        for (b, block) in blocks.iter().enumerate() {
+            for &q in block {
+                block_of[q] = b;
+            }
+        }
+
+        let mut work: Vec<usize> = (0..blocks.len()).collect();
+        let mut in_work = vec![true; blocks.len()];
+        while let Some(a) = work.pop() {
+            in_work[a] = false;
+            let splitter = blocks[a].clone();
+
+            for inverse in &inverse {
+                // states of every block which lead into the splitter
+                let mut touched: BTreeMap<usize, HashSet<usize>> = BTreeMap::new();
+                for &t in &splitter {
+                    for &q in &inverse[t] {
+                        touched.entry(block_of[q]).or_default().insert(q);
+                    }
+                }
+
+                for (y, inside) in touched {
+                    if inside.len() == blocks[y].len() {
+                        continue;
+                    }
+
+                    let (kept, moved): (Vec<usize>, Vec<usize>) = blocks[y].iter().partition(|q| inside.contains(q));
+                    let new = blocks.len();
+                    for &q in &moved {
+                        block_of[q] = new;
+                    }
+                    blocks[y] = kept;
+                    blocks.push(moved);
+
+                    if in_work[y] || blocks[new].len() <= blocks[y].len() {
+                        work.push(new);
+                        in_work.push(true);
+                    } else {
+                        work.push(y);
+                        in_work[y] = true;
+                        in_work.push(false);
+                    }
                 }
             }
         }
 
-        // remove no-used states
-        self.states.retain(|x| useful_states.contains(x));
-        self.end.retain(|x| useful_states.contains(x));
-        self.trans.retain(|x, _| useful_states.contains(x));
+        // a block is named by indices of its states, states equivalent to the dead
+        // state are dropped unless one of them is a start state.
+        let dead_block = block_of[dead];
+        let name = |b: usize| -> States { blocks[b].iter().cloned().collect() };
+        let block = |state: &States| block_of[index[state]];
+
+        let mut trans: HashMap<States, Vec<Edge>> = HashMap::new();
+        for (b, members) in blocks.iter().enumerate().filter(|&(b, _)| b != dead_block) {
+            let mut classes: Vec<(usize, Vec<(char, char)>)> = vec![];
+
+            for e in &self.trans[&states[members[0]]] {
+                let dest = block(e.next_node());
+                if dest == dead_block {
+                    continue;
+                }
+
+                let ranges = e.matches().as_ref().map_or(vec![], |x| x.ranges());
+                match classes.iter_mut().find(|(x, _)| *x == dest) {
+                    Some((_, x)) => x.extend(ranges),
+                    None => classes.push((dest, ranges)),
+                }
+            }
+
+            let edges = classes
+                .into_iter()
+                .map(|(dest, ranges)| Edge::new(name(dest), EdgeMatches::from_ranges(&normalize_ranges(ranges))))
+                .collect();
+            trans.insert(name(b), edges);
+        }
 
-        // remove epsilon edges
-        for (_, mut edges) in self.trans.iter_mut() {
-            edges.retain(|e| e.matches().is_some());
+        for start in Some(&self.start).into_iter().chain(self.context_start.values()) {
+            trans.entry(name(block(start))).or_default();
         }
 
-        // test edges intersect
-        let intersected = self.trans.values().any(|edges| {
-            edges
+        let ends = |end: &HashSet<States>| -> HashSet<States> { end.iter().map(|x| name(block(x))).collect() };
+        self.end = ends(&self.end);
+        self.context_end = self.context_end.iter().map(|(&ctx, x)| (ctx, ends(x))).collect();
+        self.start = name(block(&self.start));
+        self.context_start = self.context_start.iter().map(|(&ctx, x)| (ctx, name(block(x)))).collect();
+        self.states = trans.keys().cloned().collect();
+        self.trans = trans;
+
+        self.reset_state_mark();
+    }
+
+    // edges of the dfa state, grouped by the dfa state they lead to
+    fn dfa_edges(&self, state: &States) -> Vec<Edge> {
+        let moves: Vec<(Vec<(char, char)>, &States)> = state
+            .iter()
+            .flat_map(|&x| &self.trans[&set![x]])
+            .filter_map(|e| Some((e.matches().as_ref()?.ranges(), e.next_node())))
+            .collect();
+
+        // every range starts at a boundary and ends right before one
+        let mut bounds: Vec<char> = moves
+            .iter()
+            .flat_map(|(ranges, _)| ranges.iter().flat_map(|&(s, e)| Some(s).into_iter().chain(char_after(e))))
+            .collect();
+        bounds.sort();
+        bounds.dedup();
+
+        let mut classes: Vec<(States, Vec<(char, char)>)> = vec![];
+        for (i, &s) in bounds.iter().enumerate() {
+            let e = bounds.get(i + 1).map_or(char::MAX, |&x| char_before(x).unwrap());
+            let dests = moves
                 .iter()
-                .combinations(2)
-                .any(|pair| pair[0].intersect(pair[1]))
-        });
-        if !intersected { return; }
+                .filter(|(ranges, _)| ranges.iter().any(|&(rs, re)| rs <= s && s <= re))
+                .map(|&(_, dest)| dest);
 
+            let dest = self.closure(dests);
+            if dest.is_empty() {
+                continue;
+            }
+
+            match classes.iter_mut().find(|(x, _)| *x == dest) {
+                Some((_, ranges)) => ranges.push((s, e)),
+                None => classes.push((dest, vec![(s, e)])),
+            }
+        }
+
+        classes
+            .into_iter()
+            .map(|(dest, ranges)| Edge::new(dest, EdgeMatches::from_ranges(&normalize_ranges(ranges))))
+            .collect()
+    }
+
+    // ids of the states which can be reached by epsilon edges and are useful for the dfa
+    fn closure<'a, I: IntoIterator<Item = &'a States>>(&self, states: I) -> States {
+        let mut r = States::new();
+
+        for state in states {
+            if self.is_end(state) || self.has_nontrivial_edge(state) {
+                r.extend(state);
+            }
+
+            for x in self.epsilon_move(state) {
+                r.extend(x);
+            }
+        }
+
+        r
     }
 
     pub fn reset_state_mark(&mut self) {
-        let mut states: Vec<States> = self.states.iter().map(|x| x.clone()).collect();
+        let mut states: Vec<States> = self.states.iter().cloned().collect();
         states.sort();
 
         let mut m = HashMap::new();
@@ -143,20 +502,26 @@ impl TransTable {
             m.insert(state, set![index]);
         }
 
-        let pos = move |x: &States| m.get(&*x).unwrap().clone();
+        let pos = move |x: &States| m[x].clone();
 
         self.start = pos(&self.start);
-        self.end = self.end.iter().map(|x| pos(&*x)).collect();
-        self.states = self.states.iter().map(|x| pos(&*x)).collect();
+        self.end = self.end.iter().map(&pos).collect();
+        self.context_start = self.context_start.iter().map(|(&ctx, x)| (ctx, pos(x))).collect();
+        self.context_end = self
+            .context_end
+            .iter()
+            .map(|(&ctx, x)| (ctx, x.iter().map(&pos).collect()))
+            .collect();
+        self.states = self.states.iter().map(&pos).collect();
         self.trans = self
             .trans
             .iter()
             .map(|(state, edges)| {
                 (
-                    pos(&*state),
+                    pos(state),
                     edges
                         .iter()
-                        .map(|x| Edge::new(pos(x.next_node()), x.matches().clone()))
+                        .map(|x| x.redirect(pos(x.next_node())))
                         .collect(),
                 )
             })
@@ -167,24 +532,14 @@ impl TransTable {
         self.trans.entry(state.clone()).or_insert(vec![]).append(edges);
     }
 
-    fn posssible_nontrivial_edges(&self, state: &States) -> Vec<Edge> {
-        Iterator::flatten(
-            self.epsilon_move(state)
-                .iter()
-                .map(|x| self.trans.get(&x).unwrap()),
-        )
-        .map(|x| x.clone())
-        .collect()
-    }
-
     fn epsilon_move(&self, state: &States) -> HashSet<States> {
         let mut r: HashSet<States> = HashSet::new();
 
         self.epsilon_move_internal(state, &mut r);
 
         r.iter()
-            .filter(|&x| x != state && (self.end.contains(x) || self.has_nontrivial_edge(x)))
-            .map(|x| x.clone())
+            .filter(|&x| x != state && (self.is_end(x) || self.has_nontrivial_edge(x)))
+            .cloned()
             .collect()
     }
 
@@ -205,12 +560,8 @@ impl TransTable {
         }
     }
 
-    fn has_epsilon_edge(&self, state: &States) -> bool {
-        self.trans
-            .get(state)
-            .unwrap()
-            .iter()
-            .any(|x| x.matches().is_none())
+    fn is_end(&self, state: &States) -> bool {
+        self.end.contains(state) || self.context_end.values().any(|x| x.contains(state))
     }
 
     fn has_nontrivial_edge(&self, state: &States) -> bool {
@@ -227,7 +578,7 @@ impl fmt::Display for TransTable {
         writeln!(f, "TransTable(start: {})", self.start.iter().join(","))?;
 
         // dump states
-        let mut states = self.states.iter().map(|x| x.clone()).collect::<Vec<States>>();
+        let mut states = self.states.iter().cloned().collect::<Vec<States>>();
         states.sort();
 
         for state in states.iter() {
@@ -256,8 +607,18 @@ impl fmt::Display for TransTable {
     }
 }
 
+// dfa of a pattern, used by tests of the modules built on top of TransTable
+#[cfg(test)]
+pub(crate) fn dfa(s: &str) -> TransTable {
+    let r: ::regex_gen::RegexItem = s.into();
+    let mut t = TransTable::from_nfa(&r.nfa_graph());
+    t.as_dfa();
+    t
+}
+
 #[cfg(test)]
 mod test {
+    use execute_engine::ExecuteEngine;
     use regex_gen::RegexItem;
     use transtable::*;
 
@@ -279,20 +640,83 @@ mod test {
         let mut t = TransTable::from_nfa(&r.nfa_graph());
         t.as_dfa();
         t.reset_state_mark();
-        assert_eq!(t.state_count(), 4);
-        assert_eq!(t.edge_count(), 8);
+        assert_eq!(t.state_count(), 3);
+        assert_eq!(t.edge_count(), 3);
 
         let r: RegexItem = r#"([ab]+|c*)?"#.into();
         let mut t = TransTable::from_nfa(&r.nfa_graph());
         t.as_dfa();
         t.reset_state_mark();
-        assert_eq!(t.state_count(), 4);
-        assert_eq!(t.edge_count(), 8);
+        assert_eq!(t.state_count(), 3);
+        assert_eq!(t.edge_count(), 4);
 
         let r: RegexItem = r#"(c|[a-z])+"#.into();
         let mut t = TransTable::from_nfa(&r.nfa_graph());
         t.as_dfa();
         t.reset_state_mark();
+        assert_eq!(t.state_count(), 2);
+        assert_eq!(t.edge_count(), 2);
+    }
+
+    #[test]
+    fn test_as_dfa() {
+        for s in &[r#"(a|ab)c"#, r#"(c|[a-z])+"#, r#"[^a]*a.|\w+b"#, r#"(?i)[a-f]+|\bcafé\b"#, r#"(a*)(a*)b+"#] {
+            let r: RegexItem = (*s).into();
+            let mut t = TransTable::from_nfa(&r.nfa_graph());
+            t.as_dfa();
+
+            for edges in t.trans.values() {
+                let mut ranges: Vec<(char, char)> = edges.iter().flat_map(|x| x.matches().as_ref().unwrap().ranges()).collect();
+                ranges.sort();
+
+                assert!(ranges.windows(2).all(|x| x[0].1 < x[1].0), "{}", s);
+            }
+
+            // as_dfa on a dfa changes nothing but names of states
+            let count = (t.state_count(), t.edge_count());
+            t.as_dfa();
+            assert_eq!((t.state_count(), t.edge_count()), count);
+        }
+    }
+
+    #[test]
+    fn test_minimize() {
+        let table = |s: &str| {
+            let r: RegexItem = s.into();
+            let mut t = TransTable::from_nfa(&r.nfa_graph());
+            t.as_dfa();
+            t.minimize();
+
+            t
+        };
+
+        let t = table(r#"ab|cb"#);
+        assert_eq!(t.state_count(), 3);
+        assert_eq!(t.edge_count(), 2);
+
+        let t = table(r#"(a|b)*abb"#);
+        assert_eq!(t.state_count(), 4);
+        assert_eq!(t.edge_count(), 8);
+
+        let t = table(r#"[a-c]*|[a-c]+|a?"#);
+        assert_eq!(t.state_count(), 1);
+        assert_eq!(t.edge_count(), 1);
+
+        // dead states are removed, but the start state is always kept
+        let t = table(r#"a$b"#);
+        assert_eq!(t.state_count(), 1);
+        assert_eq!(t.edge_count(), 0);
+
+        let t1 = table(r#"x(ab|cd)*y"#);
+        let t2 = table(r#"x((ab)*|(cd)*)*y"#);
+        assert_eq!(t1.state_count(), 5);
+        assert_eq!(t2.state_count(), 5);
+        assert!(t1.compare(&t2).is_equal());
+
+        let ee = ExecuteEngine::with_transtable(table(r#"\b(ab|ac)\b|\bad\b"#));
+        assert_eq!(ee.find("x ad ab"), Some((2, 4)));
+        assert_eq!(ee.find("xac ac"), Some((4, 6)));
+        assert!(!ee.is_match("aab"));
     }
 
     #[test]